@@ -1,4 +1,4 @@
-// [("bits", commandBits, Mod, Mod), ("greetings", commandGreetings, Mod, Mod), ("giveaway", commandGiveaway, Mod, Mod), ("poll", commandPoll, Mod, Mod), ("watchtime", commandWatchtime, Mod, Mod), ("clip", commandClip, All, All), , ("genwebauth", commandWebAuth, Mod, Mod), ("listads", commandListCommercials, Mod, Mod), ("listsettings", commandListSettings, Mod, Mod), ("unmod", commandUnmod, Mod, Mod)]
+// [("bits", commandBits, Mod, Mod), ("watchtime", commandWatchtime, Mod, Mod), ("clip", commandClip, All, All), , ("genwebauth", commandWebAuth, Mod, Mod), ("listads", commandListCommercials, Mod, Mod), ("listsettings", commandListSettings, Mod, Mod), ("unmod", commandUnmod, Mod, Mod)]
 
 // [("watchtime", watchtimeVar), ("watchrank", watchrankVar), ("watchranks", watchranksVar), ("hotkey", hotkeyVar), ("obs:scene-change", obsSceneChangeVar), ("fortnite:wins", fortWinsVar), ("fortnite:kills", fortKillsVar), ("fortnite:lifewins", fortLifeWinsVar), ("fortnite:lifekills", fortLifeKillsVar), ("fortnite:solowins", fortSoloWinsVar), ("fortnite:solokills", fortSoloKillsVar), ("fortnite:duowins", fortDuoWinsVar), ("fortnite:duokills", fortDuoKillsVar), ("fortnite:squadwins", fortSquadWinsVar), ("fortnite:squadkills", fortSquadKillsVar), ("fortnite:season-solowins", fortSeasonSoloWinsVar), ("fortnite:season-solokills", fortSeasonSoloKillsVar), ("fortnite:season-duowins", fortSeasonDuoWinsVar), ("fortnite:season-duokills", fortSeasonDuoKillsVar), ("fortnite:season-squadwins", fortSeasonSquadWinsVar), ("fortnite:season-squadkills", fortSeasonSquadKillsVar)]
 
@@ -22,10 +22,11 @@ use chrono::{Utc, DateTime, FixedOffset, Duration};
 use humantime::format_duration;
 use itertools::Itertools;
 use redis::{self,Value,from_redis_value};
+use rand::Rng;
 
-pub const native_commands: [(&str, fn(Arc<IrcClient>, String, Vec<String>, Option<Message>, (Sender<Vec<String>>, Receiver<Result<Value, String>>)), bool, bool); 15] = [("echo", echo_cmd, true, true), ("set", set_cmd, true, true), ("unset", unset_cmd, true, true), ("command", command_cmd, true, true), ("title", title_cmd, false, true), ("game", game_cmd, false, true), ("notices", notices_cmd, true, true), ("moderation", moderation_cmd, true, true), ("permit", permit_cmd, true, true), ("multi", multi_cmd, false, true), ("clip", clip_cmd, true, true), ("counters", counters_cmd, true, true), ("phrases", phrases_cmd, true, true), ("commercials", commercials_cmd, true, true), ("songreq", songreq_cmd, true, false)];
+pub const native_commands: [(&str, fn(Arc<IrcClient>, String, Vec<String>, Option<Message>, (Sender<Vec<String>>, Receiver<Result<Value, String>>)), bool, bool); 25] = [("echo", echo_cmd, true, true), ("set", set_cmd, true, true), ("unset", unset_cmd, true, true), ("command", command_cmd, true, true), ("title", title_cmd, false, true), ("game", game_cmd, false, true), ("notices", notices_cmd, true, true), ("moderation", moderation_cmd, true, true), ("permit", permit_cmd, true, true), ("multi", multi_cmd, false, true), ("clip", clip_cmd, true, true), ("counters", counters_cmd, true, true), ("phrases", phrases_cmd, true, true), ("commercials", commercials_cmd, true, true), ("songreq", songreq_cmd, true, false), ("permission", permission_cmd, true, true), ("regulars", regulars_cmd, true, true), ("greetings", greetings_cmd, true, true), ("points", points_cmd, false, false), ("givepoints", givepoints_cmd, false, false), ("addpoints", addpoints_cmd, true, true), ("quote", quote_cmd, false, false), ("giveaway", giveaway_cmd, false, true), ("poll", poll_cmd, false, true), ("vote", vote_cmd, false, false)];
 
-pub const command_vars: [(&str, fn(Option<Arc<IrcClient>>, String, Option<Message>, Vec<String>, Vec<String>, (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> String); 22] = [("args", args_var), ("user", user_var), ("channel", channel_var), ("cmd", cmd_var), ("counterinc", counterinc_var), ("counter", counter_var), ("phrase", phrase_var), ("countdown", countdown_var), ("time", time_var), ("date", date_var), ("dateinc", dateinc_var), ("watchtime", watchtime_var), ("watchrank", watchrank_var), ("fortnite:wins", fortnite_wins_var), ("fortnite:kills", fortnite_kills_var), ("pubg:damage", pubg_damage_var), ("pubg:headshots", pubg_headshots_var), ("pubg:kills", pubg_kills_var), ("pubg:roadkills", pubg_roadkills_var), ("pubg:teamkills", pubg_teamkills_var), ("pubg:vehicles-destroyed", pubg_vehicles_destroyed_var), ("pubg:wins", pubg_wins_var)];
+pub const command_vars: [(&str, fn(Option<Arc<IrcClient>>, String, Option<Message>, Vec<String>, Vec<String>, (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> String); 35] = [("args", args_var), ("user", user_var), ("channel", channel_var), ("months", months_var), ("tier", tier_var), ("gifter", gifter_var), ("recipient", recipient_var), ("giftcount", giftcount_var), ("random", random_var), ("rand", rand_var), ("dice", dice_var), ("math", math_var), ("uservar", uservar_var), ("cmd", cmd_var), ("counterinc", counterinc_var), ("counterdec", counterdec_var), ("counter", counter_var), ("phrase", phrase_var), ("quote", quote_var), ("countdown", countdown_var), ("time", time_var), ("date", date_var), ("dateinc", dateinc_var), ("watchtime", watchtime_var), ("watchrank", watchrank_var), ("points", points_var), ("fortnite:wins", fortnite_wins_var), ("fortnite:kills", fortnite_kills_var), ("pubg:damage", pubg_damage_var), ("pubg:headshots", pubg_headshots_var), ("pubg:kills", pubg_kills_var), ("pubg:roadkills", pubg_roadkills_var), ("pubg:teamkills", pubg_teamkills_var), ("pubg:vehicles-destroyed", pubg_vehicles_destroyed_var), ("pubg:wins", pubg_wins_var)];
 
 pub const command_vars_async: [(&str, fn(Option<Arc<IrcClient>>, String, Option<Message>, Vec<String>, Vec<String>, (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> Option<(RequestBuilder, fn((String, (Sender<Vec<String>>, Receiver<Result<Value, String>>), Chunk)) -> String)>); 10] = [("uptime", uptime_var), ("followage", followage_var), ("subcount", subcount_var), ("followcount", followcount_var), ("urlfetch", urlfetch_var), ("spotify:playing-title", spotify_playing_title_var), ("spotify:playing-album", spotify_playing_album_var), ("spotify:playing-artist", spotify_playing_artist_var), ("youtube:latest-url", youtube_latest_url_var), ("youtube:latest-title", youtube_latest_title_var)];
 
@@ -54,14 +55,9 @@ fn cmd_var(client: Option<Arc<IrcClient>>, channel: String, irc_message: Option<
         if vargs.len() > 0 {
             let res: Result<Value,_> = redis_call(db.clone(), vec!["hget", &format!("channel:{}:commands:{}", channel, vargs[0]), "message"]);
             if let Ok(value) = res {
-                let mut message: String = from_redis_value(&value).unwrap();
-                for var in command_vars.iter() {
-                    if var.0 != "cmd" && var.0 != "var" {
-                        let args: Vec<String> = vargs[1..].iter().map(|a| a.to_string()).collect();
-                        message = parse_var(var, &message, Some(client.clone()), channel.clone(), irc_message.clone(), args, db.clone());
-                    }
-                }
-                send_parsed_message(client, channel.to_owned(), message, cargs, irc_message, db.clone(), false);
+                let message: String = from_redis_value(&value).unwrap();
+                let args: Vec<String> = vargs[1..].iter().map(|a| a.to_string()).collect();
+                send_parsed_message(client, channel.to_owned(), message, args, irc_message, db.clone(), false);
             } else {
                 for cmd in native_commands.iter() {
                     if format!("!{}", cmd.0) == vargs[0] {
@@ -131,30 +127,155 @@ fn user_var(_client: Option<Arc<IrcClient>>, _channel: String, message: Option<M
     }
 }
 
-fn channel_var(_client: Option<Arc<IrcClient>>, channel: String, _message: Option<Message>, _vargs: Vec<String>, _cargs: Vec<String>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> String {
-    let display: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:display-name", channel)]).expect(&format!("channel:{}:display-name", channel))).unwrap();
-    display
+fn months_var(_client: Option<Arc<IrcClient>>, _channel: String, message: Option<Message>, _vargs: Vec<String>, _cargs: Vec<String>, _db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> String {
+    if let Some(message) = message {
+        if let Some(months) = get_tag(&message, "msg-param-cumulative-months") {
+            months
+        } else if let Some(months) = get_tag(&message, "msg-param-months") {
+            months
+        } else {
+            "1".to_owned()
+        }
+    } else {
+        "".to_owned()
+    }
 }
 
-fn counterinc_var(_client: Option<Arc<IrcClient>>, channel: String, _message: Option<Message>, vargs: Vec<String>, _cargs: Vec<String>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> String {
+fn tier_var(_client: Option<Arc<IrcClient>>, _channel: String, message: Option<Message>, _vargs: Vec<String>, _cargs: Vec<String>, _db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> String {
+    if let Some(message) = message {
+        match get_tag(&message, "msg-param-sub-plan") {
+            Some(plan) => {
+                match plan.as_ref() {
+                    "Prime" => "Prime".to_owned(),
+                    "2000" => "2".to_owned(),
+                    "3000" => "3".to_owned(),
+                    _ => "1".to_owned()
+                }
+            }
+            None => "".to_owned()
+        }
+    } else {
+        "".to_owned()
+    }
+}
+
+fn gifter_var(_client: Option<Arc<IrcClient>>, _channel: String, message: Option<Message>, _vargs: Vec<String>, _cargs: Vec<String>, _db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> String {
+    if let Some(message) = message {
+        match get_tag(&message, "msg-id") {
+            Some(id) => {
+                if id.starts_with("anon") {
+                    "an anonymous gifter".to_owned()
+                } else if let Some(display) = get_tag(&message, "display-name") {
+                    display
+                } else {
+                    get_tag(&message, "login").unwrap_or("".to_owned())
+                }
+            }
+            None => "".to_owned()
+        }
+    } else {
+        "".to_owned()
+    }
+}
+
+fn recipient_var(_client: Option<Arc<IrcClient>>, _channel: String, message: Option<Message>, _vargs: Vec<String>, _cargs: Vec<String>, _db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> String {
+    if let Some(message) = message {
+        if let Some(display) = get_tag(&message, "msg-param-recipient-display-name") {
+            display
+        } else {
+            get_tag(&message, "msg-param-recipient-user-name").unwrap_or("".to_owned())
+        }
+    } else {
+        "".to_owned()
+    }
+}
+
+fn giftcount_var(_client: Option<Arc<IrcClient>>, _channel: String, message: Option<Message>, _vargs: Vec<String>, _cargs: Vec<String>, _db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> String {
+    if let Some(message) = message {
+        get_tag(&message, "msg-param-mass-gift-count").unwrap_or("1".to_owned())
+    } else {
+        "".to_owned()
+    }
+}
+
+fn random_var(_client: Option<Arc<IrcClient>>, _channel: String, _message: Option<Message>, vargs: Vec<String>, _cargs: Vec<String>, _db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> String {
+    let choices: Vec<String> = vargs.join(" ").split("|").map(|choice| choice.trim().to_owned()).filter(|choice| !choice.is_empty()).collect();
+    if choices.len() > 0 {
+        choices[rand::thread_rng().gen_range(0, choices.len())].to_owned()
+    } else {
+        "".to_owned()
+    }
+}
+
+fn rand_var(_client: Option<Arc<IrcClient>>, _channel: String, _message: Option<Message>, vargs: Vec<String>, _cargs: Vec<String>, _db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> String {
+    if vargs.len() > 1 {
+        let low: Result<i64,_> = vargs[0].parse();
+        let high: Result<i64,_> = vargs[1].parse();
+        if let (Ok(low), Ok(high)) = (low, high) {
+            if let (true, Some(high)) = (low <= high, high.checked_add(1)) {
+                return rand::thread_rng().gen_range(low, high).to_string();
+            }
+        }
+    }
+    "".to_owned()
+}
+
+fn dice_var(_client: Option<Arc<IrcClient>>, _channel: String, _message: Option<Message>, vargs: Vec<String>, _cargs: Vec<String>, _db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> String {
+    let rgx = Regex::new(r"^(\d*)d(\d+)([+-]\d+)?$").unwrap();
     if vargs.len() > 0 {
-        let res: Result<Value,_> = redis_call(db.clone(), vec!["hget", &format!("channel:{}:counters", channel), &vargs[0]]);
-        if let Ok(value) = res {
-            let counter: String = from_redis_value(&value).unwrap();
-            let res: Result<u16,_> = counter.parse();
-            if let Ok(num) = res {
-                redis_call(db.clone(), vec!["hset", &format!("channel:{}:counters", channel), &vargs[0], (num + 1).to_string().as_ref()]);
+        if let Some(captures) = rgx.captures(&vargs[0].to_lowercase()) {
+            let num: u16 = captures.get(1).map_or("", |m| m.as_str()).parse().unwrap_or(1);
+            let sides: u32 = captures.get(2).map_or("", |m| m.as_str()).parse().unwrap_or(0);
+            let modifier: i64 = captures.get(3).map_or("", |m| m.as_str()).parse().unwrap_or(0);
+            if num > 0 && num <= 100 && sides > 0 {
+                let mut rng = rand::thread_rng();
+                let total: i64 = (0..num).map(|_| rng.gen_range(1, sides as i64 + 1)).sum();
+                return (total + modifier).to_string();
+            }
+        }
+    }
+    "".to_owned()
+}
+
+fn math_var(_client: Option<Arc<IrcClient>>, _channel: String, _message: Option<Message>, vargs: Vec<String>, _cargs: Vec<String>, _db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> String {
+    match eval_math(&vargs.join(" ")) {
+        Some(value) => {
+            if value.fract() == 0.0 && value.abs() < 1e15 {
+                format!("{}", value as i64)
             } else {
-                redis_call(db.clone(), vec!["hset", &format!("channel:{}:counters", channel), &vargs[0], "1"]);
+                let formatted = format!("{:.2}", value);
+                formatted.trim_end_matches('0').trim_end_matches('.').to_owned()
             }
-        } else {
-            redis_call(db.clone(), vec!["hset", &format!("channel:{}:counters", channel), &vargs[0], "1"]);
         }
+        None => "".to_owned()
     }
+}
 
+fn channel_var(_client: Option<Arc<IrcClient>>, channel: String, _message: Option<Message>, _vargs: Vec<String>, _cargs: Vec<String>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> String {
+    let display: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:display-name", channel)]).expect(&format!("channel:{}:display-name", channel))).unwrap();
+    display
+}
+
+fn counterinc_var(_client: Option<Arc<IrcClient>>, channel: String, _message: Option<Message>, vargs: Vec<String>, _cargs: Vec<String>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> String {
+    if vargs.len() > 0 { add_counter(&channel, &vargs[0], 1, db.clone()); }
+    "".to_owned()
+}
+
+fn counterdec_var(_client: Option<Arc<IrcClient>>, channel: String, _message: Option<Message>, vargs: Vec<String>, _cargs: Vec<String>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> String {
+    if vargs.len() > 0 { add_counter(&channel, &vargs[0], -1, db.clone()); }
     "".to_owned()
 }
 
+fn points_var(_client: Option<Arc<IrcClient>>, channel: String, message: Option<Message>, vargs: Vec<String>, _cargs: Vec<String>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> String {
+    if vargs.len() > 0 {
+        get_points(&channel, &vargs[0].to_lowercase().replace("@", ""), db.clone()).to_string()
+    } else if let Some(message) = message {
+        get_points(&channel, &get_nick(&message), db.clone()).to_string()
+    } else {
+        "".to_owned()
+    }
+}
+
 fn followage_var(_client: Option<Arc<IrcClient>>, channel: String, message: Option<Message>, _vargs: Vec<String>, _cargs: Vec<String>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> Option<(RequestBuilder, fn((String, (Sender<Vec<String>>, Receiver<Result<Value, String>>), Chunk)) -> String)> {
     if let Some(message) = message {
         let id: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:id", channel)]).expect(&format!("channel:{}:id", channel))).unwrap();
@@ -221,7 +342,7 @@ fn counter_var(_client: Option<Arc<IrcClient>>, channel: String, _message: Optio
         let res: Result<Value,_> = redis_call(db.clone(), vec!["hget", &format!("channel:{}:counters", channel), &vargs[0]]);
         if let Ok(value) = res {
             let counter: String = from_redis_value(&value).unwrap();
-            let num: Result<u16,_> = counter.parse();
+            let num: Result<i64,_> = counter.parse();
             match num {
                 Ok(num) => num.to_string(),
                 Err(_) => "".to_owned()
@@ -248,6 +369,43 @@ fn phrase_var(_client: Option<Arc<IrcClient>>, channel: String, _message: Option
     }
 }
 
+// (uservar get [name] [@user]), (uservar set [name] [@user] [value]) or (uservar inc [name] [@user] [amount]).
+// values are stored per viewer, the viewer being the one who ran the command unless someone is mentioned
+fn uservar_var(_client: Option<Arc<IrcClient>>, channel: String, message: Option<Message>, vargs: Vec<String>, _cargs: Vec<String>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> String {
+    if vargs.len() > 1 {
+        let mut vargs = vargs;
+        let mut nick = message.map_or("".to_owned(), |message| get_nick(&message));
+        if vargs.len() > 2 && vargs[2].starts_with("@") && vargs[2].len() > 1 {
+            nick = vargs.remove(2)[1..].to_owned();
+        }
+        if nick.is_empty() { return "".to_owned() }
+        let key = format!("channel:{}:uservars:{}", channel, nick.to_lowercase());
+        match vargs[0].as_ref() {
+            "get" => {
+                let res: Result<Value,_> = redis_call(db.clone(), vec!["hget", &key, &vargs[1]]);
+                if let Ok(value) = res {
+                    let value: String = from_redis_value(&value).unwrap();
+                    return value;
+                }
+            }
+            "set" => {
+                if vargs.len() > 2 {
+                    redis_call(db.clone(), vec!["hset", &key, &vargs[1], &vargs[2..].join(" ")]);
+                } else {
+                    redis_call(db.clone(), vec!["hdel", &key, &vargs[1]]);
+                }
+            }
+            "inc" => {
+                // a single hincrby so that concurrent increments aren't lost. redis leaves the value alone if it isn't a number or would overflow
+                let amount: i64 = vargs.get(2).and_then(|amount| amount.parse().ok()).unwrap_or(1);
+                redis_call(db.clone(), vec!["hincrby", &key, &vargs[1], &amount.to_string()]);
+            }
+            _ => {}
+        }
+    }
+    "".to_owned()
+}
+
 fn time_var(_client: Option<Arc<IrcClient>>, channel: String, _message: Option<Message>, vargs: Vec<String>, _cargs: Vec<String>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> String {
     if vargs.len() > 0 {
         if let Ok(tz) = chrono_tz::Tz::from_str(&vargs[0]) {
@@ -298,6 +456,14 @@ fn dateinc_var(_client: Option<Arc<IrcClient>>, channel: String, _message: Optio
     "".to_owned()
 }
 
+fn quote_var(_client: Option<Arc<IrcClient>>, channel: String, _message: Option<Message>, vargs: Vec<String>, _cargs: Vec<String>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> String {
+    let id = if vargs.len() > 0 { Some(vargs[0].replace("#", "")) } else { random_quote(&channel, None, db.clone()) };
+    match id {
+        Some(id) => get_quote(&channel, &id, db.clone()).unwrap_or("".to_owned()),
+        None => "".to_owned()
+    }
+}
+
 fn countdown_var(_client: Option<Arc<IrcClient>>, _channel: String, _message: Option<Message>, vargs: Vec<String>, _cargs: Vec<String>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> String {
     if vargs.len() > 0 {
         let dt = DateTime::parse_from_str(&vargs[0], "%Y-%m-%dT%H:%M%z");
@@ -604,8 +770,46 @@ fn command_cmd(client: Arc<IrcClient>, channel: String, args: Vec<String>, _mess
             }
             "remove" => {
                 redis_call(db.clone(), vec!["del", &format!("channel:{}:commands:{}", channel, &args[1].to_lowercase())]);
+                redis_call(db.clone(), vec!["hdel", &format!("channel:{}:permissions", channel), &args[1].to_lowercase()]);
                 send_message(client, channel, format!("{} has been removed", &args[1]), db.clone());
             }
+            "cooldown" => {
+                if args.len() > 2 {
+                    let num: Result<u32,_> = args[2].parse();
+                    match num {
+                        Ok(_num) => {
+                            redis_call(db.clone(), vec!["hset", &format!("channel:{}:commands:{}", channel, &args[1].to_lowercase()), "cooldown", &args[2]]);
+                            send_message(client, channel, format!("{} cooldown has been set to {} seconds", &args[1], &args[2]), db.clone());
+                        }
+                        Err(_e) => {
+                            send_message(client, channel, format!("{} could not be parsed as a number", &args[2]), db.clone());
+                        }
+                    }
+                }
+            }
+            "usercooldown" => {
+                if args.len() > 2 {
+                    let num: Result<u32,_> = args[2].parse();
+                    match num {
+                        Ok(_num) => {
+                            redis_call(db.clone(), vec!["hset", &format!("channel:{}:commands:{}", channel, &args[1].to_lowercase()), "user_cooldown", &args[2]]);
+                            send_message(client, channel, format!("{} per-user cooldown has been set to {} seconds", &args[1], &args[2]), db.clone());
+                        }
+                        Err(_e) => {
+                            send_message(client, channel, format!("{} could not be parsed as a number", &args[2]), db.clone());
+                        }
+                    }
+                }
+            }
+            "cooldownmsg" => {
+                if args.len() > 2 {
+                    redis_call(db.clone(), vec!["hset", &format!("channel:{}:commands:{}", channel, &args[1].to_lowercase()), "cooldown_message", &args[2..].join(" ")]);
+                    send_message(client, channel, format!("{} cooldown message has been set", &args[1]), db.clone());
+                } else {
+                    redis_call(db.clone(), vec!["hdel", &format!("channel:{}:commands:{}", channel, &args[1].to_lowercase()), "cooldown_message"]);
+                    send_message(client, channel, format!("{} cooldown message has been removed", &args[1]), db.clone());
+                }
+            }
             "alias" => {
                 if args.len() > 2 {
                     // TODO: validate command exists
@@ -762,6 +966,33 @@ fn moderation_cmd(client: Arc<IrcClient>, channel: String, args: Vec<String>, _m
                             send_message(client, channel, format!("{} has been removed from the whitelist", &args[2]), db.clone());
                         }
                     }
+                    "block" => {
+                        if args.len() > 2 {
+                            redis_call(db.clone(), vec!["sadd", &format!("channel:{}:moderation:links:blocked", channel), &args[2].to_lowercase()]);
+                            send_message(client, channel, format!("{} has been blocked", &args[2]), db.clone());
+                        }
+                    }
+                    "unblock" => {
+                        if args.len() > 2 {
+                            redis_call(db.clone(), vec!["srem", &format!("channel:{}:moderation:links:blocked", channel), &args[2].to_lowercase()]);
+                            send_message(client, channel, format!("{} has been removed from the blocklist", &args[2]), db.clone());
+                        }
+                    }
+                    "mode" => {
+                        if args.len() > 2 {
+                            match args[2].to_lowercase().as_ref() {
+                                "allow" => {
+                                    redis_call(db.clone(), vec!["set", &format!("channel:{}:moderation:links:mode", channel), "allow"]);
+                                    send_message(client, channel, "Only whitelisted links are now allowed".to_owned(), db.clone());
+                                }
+                                "block" => {
+                                    redis_call(db.clone(), vec!["set", &format!("channel:{}:moderation:links:mode", channel), "block"]);
+                                    send_message(client, channel, "Only blocked links are now removed".to_owned(), db.clone());
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
                     "allowsubs" => {
                         redis_call(db.clone(), vec!["set", &format!("channel:{}:moderation:links:subs", channel), "true"]);
                         send_message(client, channel, "Subs are now allowed to post links".to_owned(), db.clone());
@@ -804,6 +1035,131 @@ fn moderation_cmd(client: Arc<IrcClient>, channel: String, args: Vec<String>, _m
                     _ => {}
                 }
             }
+            "symbols" => {
+                match args[1].to_lowercase().as_ref() {
+                    "set" => {
+                        if args.len() > 3 {
+                            if let Err(_e) = args[2].parse::<f32>() {
+                                send_message(client, channel, format!("{} could not be parsed as a number", &args[2]), db.clone());
+                            } else if let Err(_e) = args[3].parse::<f32>() {
+                                send_message(client, channel, format!("{} could not be parsed as a number", &args[3]), db.clone());
+                            } else {
+                                redis_call(db.clone(), vec!["set", &format!("channel:{}:moderation:symbols", channel), "true"]);
+                                redis_call(db.clone(), vec!["set", &format!("channel:{}:moderation:symbols:limit", channel), &args[2]]);
+                                redis_call(db.clone(), vec!["set", &format!("channel:{}:moderation:symbols:trigger", channel), &args[3]]);
+                                if args.len() > 4 { redis_call(db.clone(), vec!["set", &format!("channel:{}:moderation:symbols:subs", channel), &args[4]]); }
+                                send_message(client, channel, "Symbols filter has been turned on".to_owned(), db.clone());
+                            }
+                        }
+                    }
+                    "off" => {
+                        redis_call(db.clone(), vec!["del", &format!("channel:{}:moderation:symbols", channel)]);
+                        send_message(client, channel, "Symbols filter has been turned off".to_owned(), db.clone());
+                    }
+                    _ => {}
+                }
+            }
+            "length" => {
+                match args[1].to_lowercase().as_ref() {
+                    "set" => {
+                        if args.len() > 2 {
+                            let num: Result<usize,_> = args[2].parse();
+                            match num {
+                                Ok(_num) => {
+                                    redis_call(db.clone(), vec!["set", &format!("channel:{}:moderation:length", channel), "true"]);
+                                    redis_call(db.clone(), vec!["set", &format!("channel:{}:moderation:length:limit", channel), &args[2]]);
+                                    if args.len() > 3 { redis_call(db.clone(), vec!["set", &format!("channel:{}:moderation:length:subs", channel), &args[3]]); }
+                                    send_message(client, channel, "Length filter has been turned on".to_owned(), db.clone());
+                                }
+                                Err(_e) => {
+                                    send_message(client, channel, format!("{} could not be parsed as a number", &args[2]), db.clone());
+                                }
+                            }
+                        }
+                    }
+                    "off" => {
+                        redis_call(db.clone(), vec!["del", &format!("channel:{}:moderation:length", channel)]);
+                        send_message(client, channel, "Length filter has been turned off".to_owned(), db.clone());
+                    }
+                    _ => {}
+                }
+            }
+            "emotes" => {
+                match args[1].to_lowercase().as_ref() {
+                    "set" => {
+                        if args.len() > 3 {
+                            if let Err(_e) = args[2].parse::<usize>() {
+                                send_message(client, channel, format!("{} could not be parsed as a number", &args[2]), db.clone());
+                            } else if let Err(_e) = args[3].parse::<f32>() {
+                                send_message(client, channel, format!("{} could not be parsed as a number", &args[3]), db.clone());
+                            } else {
+                                redis_call(db.clone(), vec!["set", &format!("channel:{}:moderation:emotes", channel), "true"]);
+                                redis_call(db.clone(), vec!["set", &format!("channel:{}:moderation:emotes:limit", channel), &args[2]]);
+                                redis_call(db.clone(), vec!["set", &format!("channel:{}:moderation:emotes:ratio", channel), &args[3]]);
+                                if args.len() > 4 { redis_call(db.clone(), vec!["set", &format!("channel:{}:moderation:emotes:subs", channel), &args[4]]); }
+                                send_message(client, channel, "Emotes filter has been turned on".to_owned(), db.clone());
+                            }
+                        }
+                    }
+                    "off" => {
+                        redis_call(db.clone(), vec!["del", &format!("channel:{}:moderation:emotes", channel)]);
+                        send_message(client, channel, "Emotes filter has been turned off".to_owned(), db.clone());
+                    }
+                    _ => {}
+                }
+            }
+            "repeats" => {
+                match args[1].to_lowercase().as_ref() {
+                    "set" => {
+                        if args.len() > 4 {
+                            if let Err(_e) = args[2].parse::<usize>() {
+                                send_message(client, channel, format!("{} could not be parsed as a number", &args[2]), db.clone());
+                            } else if let Err(_e) = args[3].parse::<i64>() {
+                                send_message(client, channel, format!("{} could not be parsed as a number", &args[3]), db.clone());
+                            } else if let Err(_e) = args[4].parse::<f32>() {
+                                send_message(client, channel, format!("{} could not be parsed as a number", &args[4]), db.clone());
+                            } else {
+                                redis_call(db.clone(), vec!["set", &format!("channel:{}:moderation:repeats", channel), "true"]);
+                                redis_call(db.clone(), vec!["set", &format!("channel:{}:moderation:repeats:limit", channel), &args[2]]);
+                                redis_call(db.clone(), vec!["set", &format!("channel:{}:moderation:repeats:window", channel), &args[3]]);
+                                redis_call(db.clone(), vec!["set", &format!("channel:{}:moderation:repeats:similarity", channel), &args[4]]);
+                                send_message(client, channel, "Repeated messages filter has been turned on".to_owned(), db.clone());
+                            }
+                        }
+                    }
+                    "off" => {
+                        redis_call(db.clone(), vec!["del", &format!("channel:{}:moderation:repeats", channel)]);
+                        send_message(client, channel, "Repeated messages filter has been turned off".to_owned(), db.clone());
+                    }
+                    _ => {}
+                }
+            }
+            "copypasta" => {
+                match args[1].to_lowercase().as_ref() {
+                    "set" => {
+                        if args.len() > 4 {
+                            if let Err(_e) = args[2].parse::<usize>() {
+                                send_message(client, channel, format!("{} could not be parsed as a number", &args[2]), db.clone());
+                            } else if let Err(_e) = args[3].parse::<i64>() {
+                                send_message(client, channel, format!("{} could not be parsed as a number", &args[3]), db.clone());
+                            } else if let Err(_e) = args[4].parse::<f32>() {
+                                send_message(client, channel, format!("{} could not be parsed as a number", &args[4]), db.clone());
+                            } else {
+                                redis_call(db.clone(), vec!["set", &format!("channel:{}:moderation:copypasta", channel), "true"]);
+                                redis_call(db.clone(), vec!["set", &format!("channel:{}:moderation:copypasta:limit", channel), &args[2]]);
+                                redis_call(db.clone(), vec!["set", &format!("channel:{}:moderation:copypasta:window", channel), &args[3]]);
+                                redis_call(db.clone(), vec!["set", &format!("channel:{}:moderation:copypasta:similarity", channel), &args[4]]);
+                                send_message(client, channel, "Copypasta filter has been turned on".to_owned(), db.clone());
+                            }
+                        }
+                    }
+                    "off" => {
+                        redis_call(db.clone(), vec!["del", &format!("channel:{}:moderation:copypasta", channel)]);
+                        send_message(client, channel, "Copypasta filter has been turned off".to_owned(), db.clone());
+                    }
+                    _ => {}
+                }
+            }
             "age" => {
                 match args[1].to_lowercase().as_ref() {
                     "set" => {
@@ -819,6 +1175,50 @@ fn moderation_cmd(client: Arc<IrcClient>, channel: String, args: Vec<String>, _m
                     _ => {}
                 }
             }
+            "strikes" => {
+                match args[1].to_lowercase().as_ref() {
+                    "set" => {
+                        if args.len() > 2 {
+                            let steps: Vec<String> = args[2..].iter().map(|a| a.to_lowercase()).collect();
+                            let invalid: Vec<&String> = steps.iter().filter(|s| { let num: Result<u32,_> = s.parse(); *s != "delete" && *s != "ban" && num.is_err() }).collect();
+                            if invalid.len() > 0 {
+                                send_message(client, channel, format!("{} is not a valid strike action", invalid[0]), db.clone());
+                            } else {
+                                redis_call(db.clone(), vec!["del", &format!("channel:{}:moderation:strikes", channel)]);
+                                for step in steps.iter() {
+                                    redis_call(db.clone(), vec!["rpush", &format!("channel:{}:moderation:strikes", channel), step]);
+                                }
+                                send_message(client, channel, format!("Strike ladder has been set to: {}", steps.join(", ")), db.clone());
+                            }
+                        }
+                    }
+                    "decay" => {
+                        if args.len() > 2 {
+                            let num: Result<u32,_> = args[2].parse();
+                            match num {
+                                Ok(_num) => {
+                                    redis_call(db.clone(), vec!["set", &format!("channel:{}:moderation:strikes:decay", channel), &args[2]]);
+                                    send_message(client, channel, format!("Strikes will now decay after {} seconds", &args[2]), db.clone());
+                                }
+                                Err(_e) => {
+                                    send_message(client, channel, format!("{} could not be parsed as a number", &args[2]), db.clone());
+                                }
+                            }
+                        }
+                    }
+                    "reset" => {
+                        if args.len() > 2 {
+                            redis_call(db.clone(), vec!["del", &format!("channel:{}:strikes:{}", channel, args[2].to_lowercase())]);
+                            send_message(client, channel, format!("Strikes for {} have been reset", args[2].to_lowercase()), db.clone());
+                        }
+                    }
+                    "off" => {
+                        redis_call(db.clone(), vec!["del", &format!("channel:{}:moderation:strikes", channel)]);
+                        send_message(client, channel, "Strike ladder has been turned off".to_owned(), db.clone());
+                    }
+                    _ => {}
+                }
+            }
             "display" => {
                 match args[1].to_lowercase().as_ref() {
                     "on" => {
@@ -837,11 +1237,14 @@ fn moderation_cmd(client: Arc<IrcClient>, channel: String, args: Vec<String>, _m
     }
 }
 
-fn permit_cmd(client: Arc<IrcClient>, channel: String, args: Vec<String>, _message: Option<Message>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) {
+fn permit_cmd(client: Arc<IrcClient>, channel: String, args: Vec<String>, message: Option<Message>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) {
     if args.len() > 0 {
         let nick = args[0].to_lowercase();
         redis_call(db.clone(), vec!["set", &format!("channel:{}:moderation:permitted:{}", channel, nick), ""]);
         redis_call(db.clone(), vec!["expire", &format!("channel:{}:moderation:permitted:{}", channel, nick), "30"]);
+        let mut content = "".to_owned();
+        if let Some(message) = message { content = format!("permitted by {}", get_nick(&message)) }
+        log_moderation(&channel, &nick, "permit", &content, "permit", "30", db.clone());
         send_message(client, channel, format!("{} can post links for the next 30 seconds", nick), db.clone());
     }
 }
@@ -873,7 +1276,7 @@ fn clip_cmd(client: Arc<IrcClient>, channel: String, _args: Vec<String>, _messag
 fn multi_cmd(client: Arc<IrcClient>, channel: String, args: Vec<String>, _message: Option<Message>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) {
     if args.len() == 0 {
         let streams: HashSet<String> = from_redis_value(&redis_call(db.clone(), vec!["smembers", &format!("channel:{}:multi", channel)]).unwrap_or(Value::Bulk(Vec::new()))).unwrap();
-        if streams.len() > 0 { let _ = client.send_privmsg(format!("#{}", channel), format!("http://multistre.am/{}/{}", channel, streams.iter().join("/"))); }
+        if streams.len() > 0 { queue_privmsg(&client, &channel, &format!("http://multistre.am/{}/{}", channel, streams.iter().join("/")), db.clone()); }
     } else if args.len() == 1 && args[0] == "clear" {
         redis_call(db.clone(), vec!["del", &format!("channel:{}:multi", channel)]);
         send_message(client, channel, "!multi has been cleared".to_owned(), db.clone());
@@ -887,34 +1290,79 @@ fn multi_cmd(client: Arc<IrcClient>, channel: String, args: Vec<String>, _messag
 }
 
 fn counters_cmd(client: Arc<IrcClient>, channel: String, args: Vec<String>, _message: Option<Message>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) {
-    if args.len() > 1 {
+    if args.len() > 0 && args[0].to_lowercase() == "list" {
+        let counters: HashMap<String,String> = from_redis_value(&redis_call(db.clone(), vec!["hgetall", &format!("channel:{}:counters", channel)]).unwrap_or(Value::Bulk(Vec::new()))).unwrap();
+        if counters.is_empty() {
+            send_message(client, channel, "There are no counters".to_owned(), db.clone());
+        } else {
+            let list: Vec<String> = counters.iter().sorted().map(|(name, value)| format!("{} ({})", name, value)).collect();
+            send_message(client, channel, format!("Counters: {}", list.join(", ")), db.clone());
+        }
+    } else if args.len() > 1 {
         match args[0].to_lowercase().as_ref() {
             "set" => {
                 if args.len() > 2 {
-                    redis_call(db.clone(), vec!["hset", &format!("channel:{}:counters", channel), &args[1], &args[2]]);
-                    send_message(client, channel, format!("{} has been set to: {}", &args[1], &args[2]), db.clone());
+                    let res: Result<i64,_> = args[2].parse();
+                    match res {
+                        Ok(num) => {
+                            redis_call(db.clone(), vec!["hset", &format!("channel:{}:counters", channel), &args[1], &num.to_string()]);
+                            send_message(client, channel, format!("{} has been set to: {}", &args[1], num), db.clone());
+                        }
+                        Err(_) => { send_message(client, channel, format!("{} could not be parsed", &args[2]), db.clone()) }
+                    }
                 }
             }
             "inc" => {
-                let res: Result<Value,_> = redis_call(db.clone(), vec!["hget", &format!("channel:{}:counters", channel), &args[1]]);
-                if let Ok(value) = res {
-                    let counter: String = from_redis_value(&value).unwrap();
-                    let res: Result<u16,_> = counter.parse();
-                    if let Ok(num) = res {
-                        redis_call(db.clone(), vec!["hset", &format!("channel:{}:counters", channel), &args[1], (num + 1).to_string().as_ref()]);
-                    } else {
-                        redis_call(db.clone(), vec!["hset", &format!("channel:{}:counters", channel), &args[1], "1"]);
+                match add_counter(&channel, &args[1], 1, db.clone()) {
+                    Some(_num) => { send_message(client, channel, format!("{} has been increased", &args[1]), db.clone()) }
+                    None => { send_message(client, channel, format!("{} could not be increased", &args[1]), db.clone()) }
+                }
+            }
+            "dec" => {
+                match add_counter(&channel, &args[1], -1, db.clone()) {
+                    Some(_num) => { send_message(client, channel, format!("{} has been decreased", &args[1]), db.clone()) }
+                    None => { send_message(client, channel, format!("{} could not be decreased", &args[1]), db.clone()) }
+                }
+            }
+            "add" | "sub" => {
+                if args.len() > 2 {
+                    let res: Result<i64,_> = args[2].parse();
+                    match res {
+                        Ok(num) => {
+                            let amount = if args[0].to_lowercase() == "sub" { 0i64.saturating_sub(num) } else { num };
+                            match add_counter(&channel, &args[1], amount, db.clone()) {
+                                Some(num) => { send_message(client, channel, format!("{} has been set to: {}", &args[1], num), db.clone()) }
+                                None => { send_message(client, channel, format!("{} could not be changed by {}", &args[1], &args[2]), db.clone()) }
+                            }
+                        }
+                        Err(_) => { send_message(client, channel, format!("{} could not be parsed", &args[2]), db.clone()) }
                     }
+                }
+            }
+            "reset" => {
+                redis_call(db.clone(), vec!["hset", &format!("channel:{}:counters", channel), &args[1], "0"]);
+                send_message(client, channel, format!("{} has been reset", &args[1]), db.clone());
+            }
+            "del" | "delete" | "remove" => {
+                let removed: bool = from_redis_value(&redis_call(db.clone(), vec!["hdel", &format!("channel:{}:counters", channel), &args[1]]).unwrap_or(Value::Int(0))).unwrap_or(false);
+                if removed {
+                    send_message(client, channel, format!("{} has been removed", &args[1]), db.clone());
                 } else {
-                    redis_call(db.clone(), vec!["hset", &format!("channel:{}:counters", channel), &args[1], "1"]);
+                    send_message(client, channel, format!("{} doesn't exist", &args[1]), db.clone());
                 }
-                send_message(client, channel, format!("{} has been increased", &args[1]), db.clone());
             }
             _ => {}
         }
     }
 }
 
+// adds to a counter in a single hincrby so that concurrent updates aren't lost, and returns the new value.
+// a missing counter starts at 0, and redis refuses the change if the result would overflow
+fn add_counter(channel: &str, name: &str, amount: i64, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> Option<i64> {
+    let res: Result<Value,_> = redis_call(db.clone(), vec!["hincrby", &format!("channel:{}:counters", channel), name, &amount.to_string()]);
+    res.ok().and_then(|value| from_redis_value(&value).ok())
+}
+
 fn phrases_cmd(client: Arc<IrcClient>, channel: String, args: Vec<String>, _message: Option<Message>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) {
     if args.len() > 2 {
         match args[0].to_lowercase().as_ref() {
@@ -994,10 +1442,11 @@ fn commercials_cmd(client: Arc<IrcClient>, channel: String, args: Vec<String>, _
                                 if submode == "true" {
                                     let client_clone = client.clone();
                                     let channel_clone = String::from(channel.clone());
-                                    let _ = client.send_privmsg(format!("#{}", channel), "/subscribers");
+                                    let db_clone = db.clone();
+                                    queue_privmsg(&client, &channel, "/subscribers", db.clone());
                                     thread::spawn(move || {
                                         thread::sleep(time::Duration::from_secs(num * 30));
-                                        client_clone.send_privmsg(format!("#{}", channel_clone), "/subscribersoff").unwrap();
+                                        queue_privmsg(&client_clone, &channel_clone, "/subscribersoff", db_clone);
                                     });
                                 }
                                 if let Ok(value) = nres {
@@ -1084,3 +1533,456 @@ fn songreq_cmd(client: Arc<IrcClient>, channel: String, args: Vec<String>, messa
         }
     }
 }
+
+fn permission_cmd(client: Arc<IrcClient>, channel: String, args: Vec<String>, _message: Option<Message>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) {
+    if args.len() > 1 {
+        let prefix: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:settings", channel), "command:prefix"]).unwrap_or(Value::Data("!".as_bytes().to_owned()))).unwrap();
+        let mut name = args[0].to_lowercase();
+        for cmd in native_commands.iter() {
+            if format!("{}{}", prefix, cmd.0) == name { name = cmd.0.to_owned(); break; }
+        }
+        if args.len() > 2 { name = format!("{} {}", name, args[1].to_lowercase()) }
+        let role = args[args.len()-1].to_lowercase();
+        if role == "default" {
+            redis_call(db.clone(), vec!["hdel", &format!("channel:{}:permissions", channel), &name]);
+            send_message(client, channel, format!("{} permission has been reset", &name), db.clone());
+        } else {
+            match Role::parse(&role) {
+                Some(role) => {
+                    redis_call(db.clone(), vec!["hset", &format!("channel:{}:permissions", channel), &name, role.as_str()]);
+                    send_message(client, channel, format!("{} permission has been set to {}", &name, role.as_str()), db.clone());
+                }
+                None => {
+                    send_message(client, channel, format!("{} is not a valid role", &role), db.clone());
+                }
+            }
+        }
+    }
+}
+
+fn regulars_cmd(client: Arc<IrcClient>, channel: String, args: Vec<String>, _message: Option<Message>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) {
+    if args.len() > 1 {
+        let nick = args[1].to_lowercase().replace("@", "");
+        match args[0].to_lowercase().as_ref() {
+            "add" => {
+                redis_call(db.clone(), vec!["sadd", &format!("channel:{}:regulars", channel), &nick]);
+                send_message(client, channel, format!("{} has been added as a regular", &nick), db.clone());
+            }
+            "remove" => {
+                redis_call(db.clone(), vec!["srem", &format!("channel:{}:regulars", channel), &nick]);
+                send_message(client, channel, format!("{} has been removed as a regular", &nick), db.clone());
+            }
+            _ => {}
+        }
+    }
+}
+
+fn greetings_cmd(client: Arc<IrcClient>, channel: String, args: Vec<String>, _message: Option<Message>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) {
+    if args.len() > 0 {
+        match args[0].to_lowercase().as_ref() {
+            "add" => {
+                if args.len() > 3 {
+                    let nick = args[1].to_lowercase().replace("@", "");
+                    let hours: Result<u16,_> = args[2].parse();
+                    match hours {
+                        Ok(hours) => {
+                            redis_call(db.clone(), vec!["hset", &format!("channel:{}:greetings:{}", channel, nick), "message", &args[3..].join(" ")]);
+                            redis_call(db.clone(), vec!["hset", &format!("channel:{}:greetings:{}", channel, nick), "hours", &hours.to_string()]);
+                            send_message(client, channel, format!("{} will now be greeted", &nick), db.clone());
+                        }
+                        Err(_) => { send_message(client, channel, format!("{} could not be parsed", &args[2]), db.clone()) }
+                    }
+                }
+            }
+            "remove" => {
+                if args.len() > 1 {
+                    let nick = args[1].to_lowercase().replace("@", "");
+                    redis_call(db.clone(), vec!["del", &format!("channel:{}:greetings:{}", channel, nick)]);
+                    send_message(client, channel, format!("{} will no longer be greeted", &nick), db.clone());
+                }
+            }
+            "list" => {
+                let keys: Vec<String> = from_redis_value(&redis_call(db.clone(), vec!["keys", &format!("channel:{}:greetings:*", channel)]).unwrap_or(Value::Bulk(Vec::new()))).unwrap();
+                let nicks: Vec<&str> = keys.iter().map(|key| { let key: Vec<&str> = key.split(":").collect(); key[3] }).sorted().collect();
+                if nicks.is_empty() {
+                    send_message(client, channel, "There are no greetings".to_owned(), db.clone());
+                } else {
+                    send_message(client, channel, format!("Greetings: {}", nicks.join(", ")), db.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn points_cmd(client: Arc<IrcClient>, channel: String, args: Vec<String>, message: Option<Message>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) {
+    if let Some(message) = message {
+        let mut nick = get_nick(&message);
+        if args.len() > 0 { nick = args[0].to_lowercase().replace("@", "") }
+        let points = get_points(&channel, &nick, db.clone());
+        send_message(client, channel.to_owned(), format!("{} has {} {}", &nick, points, points_name(&channel, db.clone())), db.clone());
+    }
+}
+
+fn givepoints_cmd(client: Arc<IrcClient>, channel: String, args: Vec<String>, message: Option<Message>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) {
+    if let Some(message) = message {
+        if args.len() > 1 {
+            let nick = get_nick(&message);
+            let recipient = args[0].to_lowercase().replace("@", "");
+            let amount: Result<i64,_> = args[1].parse();
+            let name = points_name(&channel, db.clone());
+            let login = Regex::new(r"^[a-z0-9_]{3,25}$").unwrap();
+            match amount {
+                _ if !login.is_match(&recipient) || recipient == nick => { send_message(client, channel.to_owned(), format!("{} isn't a valid user", &args[0]), db.clone()) }
+                Ok(amount) if amount > 0 => {
+                    // the debit happens first so that two gifts at once can't both spend the same points
+                    let res: Result<Value,_> = redis_call(db.clone(), vec!["hincrby", &format!("channel:{}:points", channel), &nick, &(-amount).to_string()]);
+                    let balance: Option<i64> = res.ok().and_then(|value| from_redis_value(&value).ok());
+                    match balance {
+                        Some(balance) if balance >= 0 => {
+                            redis_call(db.clone(), vec!["hincrby", &format!("channel:{}:points", channel), &recipient, &amount.to_string()]);
+                            send_message(client, channel.to_owned(), format!("{} gave {} {} to {}", &nick, amount, &name, &recipient), db.clone());
+                        }
+                        Some(_) => {
+                            redis_call(db.clone(), vec!["hincrby", &format!("channel:{}:points", channel), &nick, &amount.to_string()]);
+                            send_message(client, channel.to_owned(), format!("{} doesn't have enough {}", &nick, &name), db.clone());
+                        }
+                        None => { send_message(client, channel.to_owned(), format!("{} doesn't have enough {}", &nick, &name), db.clone()) }
+                    }
+                }
+                _ => { send_message(client, channel.to_owned(), format!("{} could not be parsed", &args[1]), db.clone()) }
+            }
+        }
+    }
+}
+
+fn addpoints_cmd(client: Arc<IrcClient>, channel: String, args: Vec<String>, _message: Option<Message>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) {
+    if args.len() > 1 {
+        let nick = args[0].to_lowercase().replace("@", "");
+        let amount: Result<i64,_> = args[1].parse();
+        match amount {
+            Ok(amount) => {
+                let points: i64 = from_redis_value(&redis_call(db.clone(), vec!["hincrby", &format!("channel:{}:points", channel), &nick, &amount.to_string()]).unwrap_or(Value::Int(0))).unwrap_or(0);
+                send_message(client, channel.to_owned(), format!("{} now has {} {}", &nick, points, points_name(&channel, db.clone())), db.clone());
+            }
+            Err(_) => { send_message(client, channel.to_owned(), format!("{} could not be parsed", &args[1]), db.clone()) }
+        }
+    }
+}
+
+fn get_points(channel: &str, nick: &str, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> i64 {
+    let points: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:points", channel), nick]).unwrap_or(Value::Data("0".as_bytes().to_owned()))).unwrap();
+    points.parse().unwrap_or(0)
+}
+
+fn points_name(channel: &str, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> String {
+    from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:settings", channel), "points:name"]).unwrap_or(Value::Data("points".as_bytes().to_owned()))).unwrap()
+}
+
+fn quote_cmd(client: Arc<IrcClient>, channel: String, args: Vec<String>, message: Option<Message>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) {
+    if args.len() == 0 {
+        match random_quote(&channel, None, db.clone()) {
+            Some(id) => { if let Some(quote) = get_quote(&channel, &id, db.clone()) { send_message(client, channel, quote, db.clone()) } }
+            None => send_message(client, channel, "There are no quotes".to_owned(), db.clone())
+        }
+    } else {
+        let auth = message.as_ref().map_or(false, |message| get_role(&get_badges(message), &channel, &get_nick(message), db.clone()) >= get_required_role(&channel, "quote", &args, Role::Moderator, db.clone()));
+        match args[0].to_lowercase().as_ref() {
+            "add" => {
+                if args.len() > 1 {
+                    let nick = message.as_ref().map_or("".to_owned(), |message| get_nick(message));
+                    let quote = args[1..].join(" ");
+                    // the quote is stored right away so it isn't lost if looking up the game fails
+                    let qid: i64 = from_redis_value(&redis_call(db.clone(), vec!["incr", &format!("channel:{}:quoteid", channel)]).unwrap_or(Value::Int(1))).unwrap_or(1);
+                    let date = Utc::now().format("%b %-d %Y").to_string();
+                    redis_call(db.clone(), vec!["hset", &format!("channel:{}:quotes:{}", channel, qid), "quote", &quote]);
+                    redis_call(db.clone(), vec!["hset", &format!("channel:{}:quotes:{}", channel, qid), "game", ""]);
+                    redis_call(db.clone(), vec!["hset", &format!("channel:{}:quotes:{}", channel, qid), "date", &date]);
+                    redis_call(db.clone(), vec!["hset", &format!("channel:{}:quotes:{}", channel, qid), "nick", &nick]);
+                    send_message(client, channel.to_owned(), format!("Quote #{} has been added", qid), db.clone());
+
+                    // then it's stamped with whatever is currently being played
+                    let id: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:id", channel)]).expect(&format!("channel:{}:id", channel))).unwrap();
+                    let token: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:token", &channel)]).expect(&format!("channel:{}:token", &channel))).unwrap();
+                    let future = twitch_kraken_request(token, None, None, Method::GET, &format!("https://api.twitch.tv/kraken/channels/{}", &id)).send()
+                        .and_then(|mut res| { mem::replace(res.body_mut(), Decoder::empty()).concat2() })
+                        .map_err(|e| println!("request error: {}", e))
+                        .map(move |body| {
+                            let body = std::str::from_utf8(&body).unwrap().to_string();
+                            let json: Result<KrakenChannel,_> = serde_json::from_str(&body);
+                            match json {
+                                Err(e) => {
+                                    log_error(Some(Right(vec![&channel])), "quote_cmd", &e.to_string(), db.clone());
+                                    log_error(Some(Right(vec![&channel])), "request_body", &body, db.clone());
+                                }
+                                Ok(json) => { redis_call(db.clone(), vec!["hset", &format!("channel:{}:quotes:{}", channel, qid), "game", &json.game]); }
+                            }
+                        });
+                    thread::spawn(move || { tokio::run(future) });
+                }
+            }
+            "edit" => {
+                if auth && args.len() > 2 {
+                    let id = args[1].replace("#", "");
+                    let exists: bool = from_redis_value(&redis_call(db.clone(), vec!["exists", &format!("channel:{}:quotes:{}", channel, id)]).unwrap_or(Value::Int(0))).unwrap_or(false);
+                    if exists {
+                        redis_call(db.clone(), vec!["hset", &format!("channel:{}:quotes:{}", channel, id), "quote", &args[2..].join(" ")]);
+                        send_message(client, channel, format!("Quote #{} has been edited", id), db.clone());
+                    } else {
+                        send_message(client, channel, format!("Quote #{} doesn't exist", id), db.clone());
+                    }
+                }
+            }
+            "del" | "delete" | "remove" => {
+                if auth && args.len() > 1 {
+                    let id = args[1].replace("#", "");
+                    let removed: bool = from_redis_value(&redis_call(db.clone(), vec!["del", &format!("channel:{}:quotes:{}", channel, id)]).unwrap_or(Value::Int(0))).unwrap_or(false);
+                    if removed {
+                        send_message(client, channel, format!("Quote #{} has been removed", id), db.clone());
+                    } else {
+                        send_message(client, channel, format!("Quote #{} doesn't exist", id), db.clone());
+                    }
+                }
+            }
+            "search" => {
+                if args.len() > 1 {
+                    let words: Vec<String> = args[1..].iter().map(|word| word.to_lowercase()).collect();
+                    match random_quote(&channel, Some(words), db.clone()) {
+                        Some(id) => { if let Some(quote) = get_quote(&channel, &id, db.clone()) { send_message(client, channel, quote, db.clone()) } }
+                        None => send_message(client, channel, format!("No quotes match: {}", args[1..].join(" ")), db.clone())
+                    }
+                }
+            }
+            id => {
+                match get_quote(&channel, &id.replace("#", ""), db.clone()) {
+                    Some(quote) => send_message(client, channel, quote, db.clone()),
+                    None => send_message(client, channel, format!("Quote {} doesn't exist", &args[0]), db.clone())
+                }
+            }
+        }
+    }
+}
+
+fn get_quote(channel: &str, id: &str, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> Option<String> {
+    let data: HashMap<String,String> = from_redis_value(&redis_call(db.clone(), vec!["hgetall", &format!("channel:{}:quotes:{}", channel, id)]).unwrap_or(Value::Bulk(Vec::new()))).unwrap();
+    let quote = data.get("quote")?;
+    let stamp: Vec<String> = ["game", "date"].iter().filter_map(|field| data.get(*field)).filter(|value| !value.is_empty()).map(|value| value.to_owned()).collect();
+    if stamp.is_empty() {
+        Some(format!("Quote #{}: {}", id, quote))
+    } else {
+        Some(format!("Quote #{}: {} ({})", id, quote, stamp.join(", ")))
+    }
+}
+
+// picks the id of a random quote, optionally only from quotes containing every one of the given words
+fn random_quote(channel: &str, words: Option<Vec<String>>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> Option<String> {
+    let keys: Vec<String> = from_redis_value(&redis_call(db.clone(), vec!["keys", &format!("channel:{}:quotes:*", channel)]).unwrap_or(Value::Bulk(Vec::new()))).unwrap();
+    let ids: Vec<String> = keys.iter().map(|key| { let key: Vec<&str> = key.split(":").collect(); key[3].to_owned() }).filter(|id| {
+        match &words {
+            Some(words) => {
+                let quote: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:quotes:{}", channel, id), "quote"]).unwrap_or(Value::Data("".as_bytes().to_owned()))).unwrap();
+                let quote = quote.to_lowercase();
+                words.iter().all(|word| quote.contains(word))
+            }
+            None => true
+        }
+    }).collect();
+    if ids.is_empty() {
+        None
+    } else {
+        Some(ids[rand::thread_rng().gen_range(0, ids.len())].to_owned())
+    }
+}
+
+fn giveaway_cmd(client: Arc<IrcClient>, channel: String, args: Vec<String>, _message: Option<Message>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) {
+    if args.len() == 0 {
+        let res: Result<Value,_> = redis_call(db.clone(), vec!["get", &format!("channel:{}:giveaway:keyword", channel)]);
+        if let Ok(value) = res {
+            let keyword: String = from_redis_value(&value).unwrap();
+            let entries: u32 = from_redis_value(&redis_call(db.clone(), vec!["hlen", &format!("channel:{}:giveaway:entries", channel)]).unwrap_or(Value::Int(0))).unwrap_or(0);
+            send_message(client, channel, format!("The giveaway is open, type {} to enter ({} entered so far)", keyword, entries), db.clone());
+        } else {
+            send_message(client, channel, "There is no giveaway open".to_owned(), db.clone());
+        }
+    } else {
+        match args[0].to_lowercase().as_ref() {
+            "open" => {
+                if args.len() > 1 {
+                    redis_call(db.clone(), vec!["del", &format!("channel:{}:giveaway:entries", channel)]);
+                    redis_call(db.clone(), vec!["del", &format!("channel:{}:giveaway:winner", channel)]);
+                    redis_call(db.clone(), vec!["del", &format!("channel:{}:giveaway:drawn", channel)]);
+                    redis_call(db.clone(), vec!["set", &format!("channel:{}:giveaway:keyword", channel), &args[1]]);
+                    send_message(client, channel, format!("The giveaway is now open, type {} to enter", &args[1]), db.clone());
+                }
+            }
+            "close" => {
+                redis_call(db.clone(), vec!["del", &format!("channel:{}:giveaway:keyword", channel)]);
+                let entries: u32 = from_redis_value(&redis_call(db.clone(), vec!["hlen", &format!("channel:{}:giveaway:entries", channel)]).unwrap_or(Value::Int(0))).unwrap_or(0);
+                send_message(client, channel, format!("The giveaway is now closed with {} entries", entries), db.clone());
+            }
+            "draw" => {
+                let entries: HashMap<String,String> = from_redis_value(&redis_call(db.clone(), vec!["hgetall", &format!("channel:{}:giveaway:entries", channel)]).unwrap_or(Value::Bulk(Vec::new()))).unwrap();
+                let entries: Vec<(String,u32)> = entries.iter().map(|(nick, tickets)| (nick.to_owned(), tickets.parse().unwrap_or(1))).collect();
+                let total: u32 = entries.iter().map(|entry| entry.1).sum();
+                if total == 0 {
+                    send_message(client, channel, "There are no entries to draw from".to_owned(), db.clone());
+                } else {
+                    // each entry is weighted by its number of tickets
+                    let mut ticket = rand::thread_rng().gen_range(0, total);
+                    let mut winner = entries[0].0.to_owned();
+                    for entry in entries.iter() {
+                        if ticket < entry.1 { winner = entry.0.to_owned(); break }
+                        ticket -= entry.1;
+                    }
+                    // the winner can't be drawn twice if they fail to claim, or enter again while the giveaway is open
+                    redis_call(db.clone(), vec!["hdel", &format!("channel:{}:giveaway:entries", channel), &winner]);
+                    redis_call(db.clone(), vec!["sadd", &format!("channel:{}:giveaway:drawn", channel), &winner]);
+
+                    let claim: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:settings", channel), "giveaway:claim"]).unwrap_or(Value::Data("60".as_bytes().to_owned()))).unwrap();
+                    let claim: u64 = claim.parse().unwrap_or(60);
+                    let keyword: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:giveaway:keyword", channel)]).unwrap_or(Value::Data("".as_bytes().to_owned()))).unwrap();
+                    let id: i64 = from_redis_value(&redis_call(db.clone(), vec!["incr", &format!("channel:{}:giveawayid", channel)]).unwrap_or(Value::Int(1))).unwrap_or(1);
+                    redis_call(db.clone(), vec!["hset", &format!("channel:{}:giveaways:{}", channel, id), "winner", &winner]);
+                    redis_call(db.clone(), vec!["hset", &format!("channel:{}:giveaways:{}", channel, id), "keyword", &keyword]);
+                    redis_call(db.clone(), vec!["hset", &format!("channel:{}:giveaways:{}", channel, id), "entries", &entries.len().to_string()]);
+                    redis_call(db.clone(), vec!["hset", &format!("channel:{}:giveaways:{}", channel, id), "date", &Utc::now().format("%b %-d %Y").to_string()]);
+                    redis_call(db.clone(), vec!["hset", &format!("channel:{}:giveaways:{}", channel, id), "claimed", "false"]);
+                    redis_call(db.clone(), vec!["set", &format!("channel:{}:giveaway:winner", channel), &format!("{} {}", winner, id), "EX", &claim.to_string()]);
+                    send_message(client.clone(), channel.to_owned(), format!("{} has won the giveaway! Type anything in chat within {} seconds to claim the prize", &winner, claim), db.clone());
+
+                    thread::spawn(move || {
+                        thread::sleep(time::Duration::from_secs(claim));
+                        let claimed: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:giveaways:{}", channel, id), "claimed"]).unwrap_or(Value::Data("true".as_bytes().to_owned()))).unwrap();
+                        if claimed != "true" {
+                            send_message(client, channel.to_owned(), format!("{} didn't claim the prize in time", &winner), db.clone());
+                        }
+                    });
+                }
+            }
+            "history" => {
+                let last: i64 = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:giveawayid", channel)]).unwrap_or(Value::Data("0".as_bytes().to_owned()))).unwrap_or(0);
+                let mut draws: Vec<String> = Vec::new();
+                for id in (1..last + 1).rev() {
+                    if draws.len() == 5 { break }
+                    let data: HashMap<String,String> = from_redis_value(&redis_call(db.clone(), vec!["hgetall", &format!("channel:{}:giveaways:{}", channel, id)]).unwrap_or(Value::Bulk(Vec::new()))).unwrap();
+                    if let (Some(winner), Some(date)) = (data.get("winner"), data.get("date")) {
+                        if data.get("claimed").map_or(false, |claimed| claimed == "true") {
+                            draws.push(format!("{} ({})", winner, date));
+                        } else {
+                            draws.push(format!("{} ({}, unclaimed)", winner, date));
+                        }
+                    }
+                }
+                if draws.is_empty() {
+                    send_message(client, channel, "There are no past giveaways".to_owned(), db.clone());
+                } else {
+                    send_message(client, channel, format!("Past winners: {}", draws.join(", ")), db.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn poll_cmd(client: Arc<IrcClient>, channel: String, args: Vec<String>, _message: Option<Message>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) {
+    if args.len() == 0 {
+        match poll_results(&channel, db.clone()) {
+            Some(results) => send_message(client, channel, results, db.clone()),
+            None => send_message(client, channel, "There is no poll".to_owned(), db.clone())
+        }
+    } else {
+        match args[0].to_lowercase().as_ref() {
+            "start" => {
+                // !poll start [seconds] "question" option | option ...
+                let mut rest = args[1..].to_vec();
+                let mut duration: u64 = 120;
+                if rest.len() > 0 {
+                    // polls run for at most a day
+                    if let Ok(secs) = rest[0].parse::<u64>() { duration = secs.min(86400); rest.remove(0); }
+                }
+                let rest = rest.join(" ");
+                let rgx = Regex::new(r#"^"([^"]+)"\s+(.+)$"#).unwrap();
+                let parsed = rgx.captures(&rest).map(|captures| {
+                    let question = captures[1].trim().to_owned();
+                    let options: Vec<String> = captures[2].split("|").map(|option| option.trim().to_owned()).filter(|option| !option.is_empty()).collect();
+                    (question, options)
+                }).filter(|(_, options)| options.len() > 1);
+                match parsed {
+                    Some((question, options)) => {
+                        let id: i64 = from_redis_value(&redis_call(db.clone(), vec!["incr", &format!("channel:{}:pollid", channel)]).unwrap_or(Value::Int(1))).unwrap_or(1);
+                        let ends = Utc::now() + Duration::seconds(duration as i64);
+                        redis_call(db.clone(), vec!["del", &format!("channel:{}:poll:options", channel)]);
+                        redis_call(db.clone(), vec!["del", &format!("channel:{}:poll:votes", channel)]);
+                        for option in options.iter() {
+                            redis_call(db.clone(), vec!["rpush", &format!("channel:{}:poll:options", channel), option]);
+                        }
+                        redis_call(db.clone(), vec!["hset", &format!("channel:{}:poll", channel), "id", &id.to_string()]);
+                        redis_call(db.clone(), vec!["hset", &format!("channel:{}:poll", channel), "question", &question]);
+                        redis_call(db.clone(), vec!["hset", &format!("channel:{}:poll", channel), "ends", &ends.to_rfc3339()]);
+                        redis_call(db.clone(), vec!["hset", &format!("channel:{}:poll", channel), "open", "true"]);
+                        let numbered: Vec<String> = options.iter().enumerate().map(|(i, option)| format!("{}) {}", i + 1, option)).collect();
+                        send_message(client.clone(), channel.to_owned(), format!("Poll: {} {} Vote with !vote [number] or by typing the option", &question, numbered.join(" ")), db.clone());
+
+                        thread::spawn(move || {
+                            thread::sleep(time::Duration::from_secs(duration));
+                            // a newer poll may have been started, or this one closed early
+                            let current: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:poll", channel), "id"]).unwrap_or(Value::Data("".as_bytes().to_owned()))).unwrap();
+                            if current == id.to_string() { close_poll(client, channel, db) }
+                        });
+                    }
+                    None => { send_message(client, channel, "Usage: !poll start [seconds] \"question\" option | option".to_owned(), db.clone()) }
+                }
+            }
+            "close" | "end" => { close_poll(client, channel, db) }
+            _ => {}
+        }
+    }
+}
+
+fn vote_cmd(_client: Arc<IrcClient>, channel: String, args: Vec<String>, message: Option<Message>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) {
+    if let Some(message) = message {
+        if args.len() > 0 { cast_vote(&channel, &get_nick(&message), &args.join(" "), true, db.clone()); }
+    }
+}
+
+// records a vote in the open poll, by option number or by the option's text. only the first vote of each user counts
+pub fn cast_vote(channel: &str, nick: &str, choice: &str, numbered: bool, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> bool {
+    let open: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:poll", channel), "open"]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
+    if open != "true" { return false }
+    let options: Vec<String> = from_redis_value(&redis_call(db.clone(), vec!["lrange", &format!("channel:{}:poll:options", channel), "0", "-1"]).unwrap_or(Value::Bulk(Vec::new()))).unwrap();
+    let choice = choice.trim().to_lowercase();
+    let mut index = options.iter().position(|option| option.to_lowercase() == choice);
+    if index.is_none() && numbered {
+        if let Ok(num) = choice.parse::<usize>() {
+            if num > 0 && num <= options.len() { index = Some(num - 1) }
+        }
+    }
+    match index {
+        Some(index) => from_redis_value(&redis_call(db.clone(), vec!["hsetnx", &format!("channel:{}:poll:votes", channel), nick, &(index + 1).to_string()]).unwrap_or(Value::Int(0))).unwrap_or(false),
+        None => false
+    }
+}
+
+fn close_poll(client: Arc<IrcClient>, channel: String, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) {
+    let open: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:poll", channel), "open"]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
+    if open == "true" {
+        redis_call(db.clone(), vec!["hset", &format!("channel:{}:poll", channel), "open", "false"]);
+        redis_call(db.clone(), vec!["hset", &format!("channel:{}:poll", channel), "ends", &Utc::now().to_rfc3339()]);
+        if let Some(results) = poll_results(&channel, db.clone()) {
+            send_message(client, channel, format!("The poll has closed! {}", results), db.clone());
+        }
+    }
+}
+
+fn poll_results(channel: &str, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> Option<String> {
+    let question: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:poll", channel), "question"]).ok()?).unwrap();
+    let options: Vec<String> = from_redis_value(&redis_call(db.clone(), vec!["lrange", &format!("channel:{}:poll:options", channel), "0", "-1"]).unwrap_or(Value::Bulk(Vec::new()))).unwrap();
+    let votes: HashMap<String,String> = from_redis_value(&redis_call(db.clone(), vec!["hgetall", &format!("channel:{}:poll:votes", channel)]).unwrap_or(Value::Bulk(Vec::new()))).unwrap();
+    let total = votes.len();
+    let results: Vec<String> = options.iter().enumerate().map(|(i, option)| {
+        let count = votes.values().filter(|vote| **vote == (i + 1).to_string()).count();
+        let percent = if total > 0 { count * 100 / total } else { 0 };
+        format!("{}: {} ({}%)", option, count, percent)
+    }).collect();
+    Some(format!("{} {}", question, results.join(", ")))
+}
@@ -4,6 +4,7 @@
 #[macro_use] extern crate rocket;
 
 mod commands;
+mod template;
 mod types;
 mod util;
 mod web;
@@ -23,7 +24,7 @@ use flexi_logger::{Criterion,Naming,Cleanup,Duplicate,Logger};
 use crossbeam_channel::{bounded,unbounded,Sender,Receiver,TryRecvError,RecvTimeoutError};
 use irc::client::prelude::*;
 use url::Url;
-use regex::{Regex,RegexBuilder};
+use regex::RegexBuilder;
 use serde_json::value::Value::Number;
 use chrono::{Utc, DateTime, NaiveTime, Timelike};
 use http::header::{self,HeaderValue};
@@ -111,23 +112,45 @@ fn run_reactor(bots: HashMap<String, (HashSet<String>, Config)>, db: (Sender<Vec
         let bot = bot.clone();
         let channels = channels.clone();
         thread::spawn(move || {
+            let mut backoff: u64 = 5;
             loop {
                 let db = db.clone();
                 let mut chans: Vec<&str> = Vec::new();
                 for chan in (channels.0).iter() {
                     chans.push(chan);
                 }
+
+                // reload the token in case it was refreshed while we were connected
+                let mut config = channels.1.clone();
+                let res: Result<Value,_> = redis_call(db.clone(), vec!["get", &format!("bot:{}:token", bot)]);
+                if let Ok(value) = res {
+                    let passphrase: String = from_redis_value(&value).unwrap();
+                    config.password = Some(format!("oauth:{}", passphrase));
+                }
+
                 log_info(Some(Right(chans.clone())), "run_reactor", "connecting to irc", db.clone());
+                let mut reactor = IrcReactor::new().unwrap();
+                let client = match reactor.prepare_client_and_connect(&config) {
+                    Ok(client) => Arc::new(client),
+                    Err(e) => {
+                        log_error(Some(Right(chans)), "run_reactor", &format!("failed to connect, retrying in {} seconds: {}", backoff, e), db.clone());
+                        thread::sleep(time::Duration::from_secs(backoff));
+                        backoff = (backoff * 2).min(600);
+                        continue;
+                    }
+                };
                 let (senderC, receiverC) = bounded(0);
                 let (senderA, receiverA) = unbounded();
+                let (senderQ, receiverQ) = unbounded();
                 let mut senders: HashMap<String, Vec<Sender<ThreadAction>>> = HashMap::new();
-                let mut reactor = IrcReactor::new().unwrap();
-                let client = Arc::new(reactor.prepare_client_and_connect(&channels.1).unwrap());
                 let _ = client.identify();
                 let _ = client.send("CAP REQ :twitch.tv/tags");
                 let _ = client.send("CAP REQ :twitch.tv/commands");
                 register_handler(bot.clone(), (*client).clone(), &mut reactor, db.clone());
                 client_listener(client.clone(), db.clone(), receiverC);
+                // anything left over from before the connection dropped is stale by now, including moderation commands
+                redis_call(db.clone(), vec!["del", &format!("bot:{}:queue", bot)]);
+                message_queue(bot.clone(), client.clone(), db.clone(), receiverQ);
                 for channel in channels.0.iter() {
                     let (sender1, receiver1) = unbounded();
                     let (sender2, receiver2) = unbounded();
@@ -142,23 +165,28 @@ fn run_reactor(bots: HashMap<String, (HashSet<String>, Config)>, db: (Sender<Vec
                     run_commercials(db.clone(), channel.to_owned(), senderC.clone(), receiver5);
                 }
                 channel_authcheck(db.clone(), chans.iter().map(|c| c.to_string()).collect(), senders.clone(), senderC.clone(), receiverA);
+
+                // the reactor only returns once the connection drops, either from an error or a ping timeout
+                let connected = time::Instant::now();
                 let res = reactor.run();
-                match res {
-                    Err(e) => {
-                        log_error(Some(Right(chans)), "run_reactor", &e.to_string(), db.clone());
-                        let _ = senderA.send(ThreadAction::Kill);
-                        for channel in channels.0.iter() {
-                            if let Some(senders) = senders.get(channel) {
-                                for sender in senders {
-                                    let _ = sender.send(ThreadAction::Kill);
-                                }
-                            }
+                let _ = senderA.send(ThreadAction::Kill);
+                let _ = senderQ.send(ThreadAction::Kill);
+                for channel in channels.0.iter() {
+                    if let Some(senders) = senders.get(channel) {
+                        for sender in senders {
+                            let _ = sender.send(ThreadAction::Kill);
                         }
-
-                        thread::sleep(time::Duration::from_secs(60));
                     }
-                    Ok(_) => { break }
                 }
+
+                // start over from the shortest delay if the connection was stable for a while
+                if connected.elapsed().as_secs() > 600 { backoff = 5 }
+                match res {
+                    Err(e) => { log_error(Some(Right(chans)), "run_reactor", &format!("disconnected, reconnecting in {} seconds: {}", backoff, e), db.clone()) }
+                    Ok(_) => { log_error(Some(Right(chans)), "run_reactor", &format!("disconnected, reconnecting in {} seconds", backoff), db.clone()) }
+                }
+                thread::sleep(time::Duration::from_secs(backoff));
+                backoff = (backoff * 2).min(600);
             }
         });
     });
@@ -187,6 +215,46 @@ fn register_handler(bot: String, client: IrcClient, reactor: &mut IrcReactor, db
                             }
                         }
                     }
+                } else if cmd == "USERNOTICE" {
+                    if let (Some(chan), Some(id)) = (chans.get(0), get_tag(&irc_message, "msg-id")) {
+                        let channel = &chan[1..];
+                        let login = get_tag(&irc_message, "login").unwrap_or("".to_owned());
+                        let setting = match id.as_ref() {
+                            "sub" => Some("channel:sub-message"),
+                            "resub" => Some("channel:resub-message"),
+                            "subgift" => Some("channel:subgift-message"),
+                            "submysterygift" => Some("channel:mysterygift-message"),
+                            "anonsubgift" | "anonsubmysterygift" => Some("channel:anonsubgift-message"),
+                            _ => None
+                        };
+
+                        if id == "raid" { run_shoutout(clientC.clone(), channel.to_owned(), irc_message.clone(), db.clone()) }
+
+                        // a mystery gift is followed by a subgift notice for every recipient, only announce the mystery gift
+                        let mut announce = true;
+                        if id == "submysterygift" || id == "anonsubmysterygift" {
+                            let count = get_tag(&irc_message, "msg-param-mass-gift-count").unwrap_or("1".to_owned());
+                            redis_call(db.clone(), vec!["set", &format!("channel:{}:subs:mysterygift:{}", channel, login), &count]);
+                            redis_call(db.clone(), vec!["expire", &format!("channel:{}:subs:mysterygift:{}", channel, login), "60"]);
+                        } else if id == "subgift" || id == "anonsubgift" {
+                            let res: Result<Value,_> = redis_call(db.clone(), vec!["decr", &format!("channel:{}:subs:mysterygift:{}", channel, login)]);
+                            if let Ok(value) = res {
+                                let remaining: i64 = from_redis_value(&value).unwrap_or(-1);
+                                if remaining >= 0 { announce = false }
+                                if remaining <= 0 { redis_call(db.clone(), vec!["del", &format!("channel:{}:subs:mysterygift:{}", channel, login)]); }
+                            }
+                        }
+
+                        if let (true, Some(setting)) = (announce, setting) {
+                            let res: Result<Value,_> = redis_call(db.clone(), vec!["hget", &format!("channel:{}:settings", channel), setting]);
+                            if let Ok(value) = res {
+                                let message: String = from_redis_value(&value).unwrap();
+                                if !message.is_empty() {
+                                    send_parsed_message(clientC.clone(), channel.to_owned(), message, Vec::new(), Some(irc_message.clone()), db.clone(), false);
+                                }
+                            }
+                        }
+                    }
                 }
             }
             Command::PRIVMSG(chan, msg) => {
@@ -206,12 +274,21 @@ fn register_handler(bot: String, client: IrcClient, reactor: &mut IrcReactor, db
                         let mut subscriber = false;
                         if let Some(_value) = badges.get("subscriber") { subscriber = true }
 
-                        let mut auth = false;
-                        if let Some(_value) = badges.get("broadcaster") { auth = true }
-                        if let Some(_value) = badges.get("moderator") { auth = true }
+                        let role = get_role(&badges, channel, &nick, db.clone());
+                        let auth = role >= Role::Moderator;
+
+                        // remember recent chatters and subscribers for loyalty point bonuses
+                        let points: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:settings", channel), "points:enabled"]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
+                        if points == "true" {
+                            redis_call(db.clone(), vec!["set", &format!("channel:{}:points:active:{}", channel, &nick), "true", "EX", "600"]);
+                            if subscriber {
+                                redis_call(db.clone(), vec!["sadd", &format!("channel:{}:points:subs", channel), &nick]);
+                            } else {
+                                redis_call(db.clone(), vec!["srem", &format!("channel:{}:points:subs", channel), &nick]);
+                            }
+                        }
 
                         if let Some(donated) = get_bits(&irc_message) {
-                            // TODO: queue agent actions
                             let mut bits: u16 = 0;
                             let keys: Vec<String> = from_redis_value(&redis_call(db.clone(), vec!["keys", &format!("channel:{}:events:bits:*", channel)]).unwrap_or(Value::Bulk(Vec::new()))).unwrap();
                             for key in keys {
@@ -225,39 +302,70 @@ fn register_handler(bot: String, client: IrcClient, reactor: &mut IrcReactor, db
                                 }
                             }
                             if bits > 0 {
-                                let etype: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:events:bits:{}", channel, bits), "type"]).expect(&format!("channel:{}:events:bits:{}", channel, bits))).unwrap();
+                                let etype: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:events:bits:{}", channel, bits), "type"]).unwrap_or(Value::Data("".as_bytes().to_owned()))).unwrap();
                                 match etype.as_ref() {
                                     "local" => {
                                         let ids: Vec<String> = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:events:bits:{}", channel, bits), "actions"]).unwrap_or(Value::Bulk(Vec::new()))).unwrap();
                                         redis_call(db.clone(), vec!["lpush", &format!("channel:{}:local:actions", channel), &ids.join(" ")]);
                                     }
+                                    "command" => {
+                                        let name: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:events:bits:{}", channel, bits), "command"]).unwrap_or(Value::Data("".as_bytes().to_owned()))).unwrap();
+                                        let res: Result<Value,_> = redis_call(db.clone(), vec!["hget", &format!("channel:{}:commands:{}", channel, name.to_lowercase()), "message"]);
+                                        if let Ok(value) = res {
+                                            let message: String = from_redis_value(&value).unwrap();
+                                            send_parsed_message(client.clone(), channel.to_owned(), message, args.clone(), Some(irc_message.clone()), db.clone(), false);
+                                        }
+                                    }
+                                    "counter" => {
+                                        let res: Result<Value,_> = redis_call(db.clone(), vec!["hget", &format!("channel:{}:events:bits:{}", channel, bits), "counter"]);
+                                        if let Ok(value) = res {
+                                            let counter: String = from_redis_value(&value).unwrap();
+                                            redis_call(db.clone(), vec!["hincrby", &format!("channel:{}:counters", channel), &counter, "1"]);
+                                        }
+                                    }
+                                    "thanks" => {
+                                        let res: Result<Value,_> = redis_call(db.clone(), vec!["hget", &format!("channel:{}:events:bits:{}", channel, bits), "message"]);
+                                        if let Ok(value) = res {
+                                            let message: String = from_redis_value(&value).unwrap();
+                                            let message = replace_var("bits", &donated, &message);
+                                            send_parsed_message(client.clone(), channel.to_owned(), message, args.clone(), Some(irc_message.clone()), db.clone(), false);
+                                        }
+                                    }
+                                    "songreq" => {
+                                        if let Some(id) = youtube_id(&msg) {
+                                            for cmd in commands::native_commands.iter() {
+                                                if cmd.0 == "songreq" { (cmd.1)(client.clone(), channel.to_owned(), vec![id.to_owned()], Some(irc_message.clone()), db.clone()) }
+                                            }
+                                        }
+                                    }
                                     _ => {}
                                 }
                             }
                         }
 
-                        // moderate incoming messages
-                        // TODO: symbols, length
+                        // moderate incoming messages. only one strike is recorded per message
+                        let mut struck = false;
                         if !auth {
-                            let display: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:display", channel)]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
                             let caps: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:caps", channel)]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
                             let colors: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:colors", channel)]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
+                            let symbols: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:symbols", channel)]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
+                            let length: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:length", channel)]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
+                            let emotes: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:emotes", channel)]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
+                            let repeats: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:repeats", channel)]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
+                            let copypasta: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:copypasta", channel)]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
                             let links: HashSet<String> = from_redis_value(&redis_call(db.clone(), vec!["smembers", &format!("channel:{}:moderation:links", channel)]).unwrap_or(Value::Bulk(Vec::new()))).unwrap();
+                            let blocked: HashSet<String> = from_redis_value(&redis_call(db.clone(), vec!["smembers", &format!("channel:{}:moderation:links:blocked", channel)]).unwrap_or(Value::Bulk(Vec::new()))).unwrap();
                             let bkeys: Vec<String> = from_redis_value(&redis_call(db.clone(), vec!["keys", &format!("channel:{}:moderation:blacklist:*", channel)]).unwrap_or(Value::Bulk(Vec::new()))).unwrap();
                             let age: Result<Value,_> = redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:age", channel)]);
+                            let message_id = get_message_id(&irc_message);
                             if colors == "true" && msg.len() > 6 && msg.as_bytes()[0] == 1 && &msg[1..7] == "ACTION" {
-                                let _ = client.send_privmsg(chan, format!("/timeout {} 1", nick));
-                                if display == "true" { send_message(client.clone(), channel.to_owned(), format!("@{} you've been timed out for posting colors", nick), db.clone()); }
-                            }
-                            if let Ok(value) = age {
-                                let age: String = from_redis_value(&value).unwrap();
-                                let res: Result<i64,_> = age.parse();
-                                if let Ok(age) = res { spawn_age_check(client.clone(), db.clone(), channel.to_string(), nick.clone(), age, display.to_string()); }
+                                strike(client.clone(), channel, &nick, message_id.clone(), &msg, "colors", "1", db.clone());
+                                struck = true;
                             }
-                            if caps == "true" {
-                                let limit: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:caps:limit", channel)]).expect(&format!("channel:{}:moderation:caps:limit", channel))).unwrap();
-                                let trigger: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:caps:trigger", channel)]).expect(&format!("channel:{}:moderation:caps:trigger", channel))).unwrap();
-                                let subs: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:caps:subs", channel)]).expect(&format!("channel:{}:moderation:caps:subs", channel))).unwrap();
+                            if caps == "true" && !struck {
+                                let limit: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:caps:limit", channel)]).unwrap_or(Value::Data("100".as_bytes().to_owned()))).unwrap();
+                                let trigger: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:caps:trigger", channel)]).unwrap_or(Value::Data("0".as_bytes().to_owned()))).unwrap();
+                                let subs: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:caps:subs", channel)]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
                                 let limit: Result<f32,_> = limit.parse();
                                 let trigger: Result<f32,_> = trigger.parse();
                                 if let (Ok(limit), Ok(trigger)) = (limit, trigger) {
@@ -267,46 +375,148 @@ fn register_handler(bot: String, client: IrcClient, reactor: &mut IrcReactor, db
                                         let ratio = num / len;
                                         if ratio >= (limit / 100.0) {
                                             if !subscriber || subscriber && subs != "true" {
-                                                let _ = client.send_privmsg(chan, format!("/timeout {} 1", nick));
-                                                if display == "true" { send_message(client.clone(), channel.to_owned(), format!("@{} you've been timed out for posting too many caps", nick), db.clone()); }
+                                                strike(client.clone(), channel, &nick, message_id.clone(), &msg, "caps", "1", db.clone());
+                                                struck = true;
                                             }
                                         }
                                     }
                                 }
                             }
-                            if links.len() > 0 && url_regex().is_match(&msg) {
+                            if symbols == "true" && !struck {
+                                let limit: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:symbols:limit", channel)]).unwrap_or(Value::Data("100".as_bytes().to_owned()))).unwrap();
+                                let trigger: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:symbols:trigger", channel)]).unwrap_or(Value::Data("0".as_bytes().to_owned()))).unwrap();
+                                let subs: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:symbols:subs", channel)]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
+                                let limit: Result<f32,_> = limit.parse();
+                                let trigger: Result<f32,_> = trigger.parse();
+                                if let (Ok(limit), Ok(trigger)) = (limit, trigger) {
+                                    // whitespace doesn't count towards either side of the ratio
+                                    let len = msg.chars().filter(|c| !c.is_whitespace()).count() as f32;
+                                    if len > 0.0 && len >= trigger {
+                                        let num = msg.chars().filter(|c| !c.is_alphanumeric() && !c.is_whitespace()).count() as f32;
+                                        let ratio = num / len;
+                                        if ratio >= (limit / 100.0) {
+                                            if !subscriber || subscriber && subs != "true" {
+                                                strike(client.clone(), channel, &nick, message_id.clone(), &msg, "symbols", "1", db.clone());
+                                                struck = true;
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                            if length == "true" && !struck {
+                                let limit: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:length:limit", channel)]).unwrap_or(Value::Data("500".as_bytes().to_owned()))).unwrap();
+                                let subs: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:length:subs", channel)]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
+                                let limit: Result<usize,_> = limit.parse();
+                                if let Ok(limit) = limit {
+                                    if msg.chars().count() > limit {
+                                        if !subscriber || subscriber && subs != "true" {
+                                            strike(client.clone(), channel, &nick, message_id.clone(), &msg, "length", "1", db.clone());
+                                            struck = true;
+                                        }
+                                    }
+                                }
+                            }
+                            if emotes == "true" && !struck {
+                                let limit: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:emotes:limit", channel)]).unwrap_or(Value::Data("0".as_bytes().to_owned()))).unwrap();
+                                let ratio: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:emotes:ratio", channel)]).unwrap_or(Value::Data("0".as_bytes().to_owned()))).unwrap();
+                                let subs: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:emotes:subs", channel)]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
+                                let limit: Result<usize,_> = limit.parse();
+                                let ratio: Result<f32,_> = ratio.parse();
+                                if let (Ok(limit), Ok(ratio)) = (limit, ratio) {
+                                    let ranges = get_emotes(&irc_message);
+                                    let len = msg.chars().filter(|c| !c.is_whitespace()).count() as f32;
+                                    let num = ranges.iter().fold(0.0, |acc, r| acc + (r.2 - r.1 + 1) as f32);
+                                    // a limit or ratio of 0 leaves that check disabled
+                                    if (limit > 0 && ranges.len() > limit) || (ratio > 0.0 && len > 0.0 && num / len > (ratio / 100.0)) {
+                                        if !subscriber || subscriber && subs != "true" {
+                                            strike(client.clone(), channel, &nick, message_id.clone(), &msg, "emotes", "1", db.clone());
+                                            struck = true;
+                                        }
+                                    }
+                                }
+                            }
+                            if (repeats == "true" || copypasta == "true") && !struck {
+                                let normalized: String = msg.to_lowercase().split_whitespace().collect::<Vec<&str>>().join(" ");
+                                let now = Utc::now().timestamp();
+                                if repeats == "true" {
+                                    let limit: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:repeats:limit", channel)]).unwrap_or(Value::Data("3".as_bytes().to_owned()))).unwrap();
+                                    let window: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:repeats:window", channel)]).unwrap_or(Value::Data("30".as_bytes().to_owned()))).unwrap();
+                                    let cutoff: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:repeats:similarity", channel)]).unwrap_or(Value::Data("90".as_bytes().to_owned()))).unwrap();
+                                    let limit: Result<usize,_> = limit.parse();
+                                    let window: Result<i64,_> = window.parse();
+                                    let cutoff: Result<f32,_> = cutoff.parse();
+                                    if let (Ok(limit), Ok(window), Ok(cutoff)) = (limit, window, cutoff) {
+                                        let recent: Vec<String> = from_redis_value(&redis_call(db.clone(), vec!["lrange", &format!("channel:{}:recent:{}", channel, nick), "0", "-1"]).unwrap_or(Value::Bulk(Vec::new()))).unwrap();
+                                        let count = recent.iter().filter(|entry| {
+                                            let entry: Vec<&str> = entry.splitn(2, " ").collect();
+                                            let timestamp: Result<i64,_> = entry[0].parse();
+                                            if let (Ok(timestamp), Some(text)) = (timestamp, entry.get(1)) {
+                                                now - timestamp <= window && similarity(text, &normalized) >= (cutoff / 100.0)
+                                            } else { false }
+                                        }).count();
+                                        redis_call(db.clone(), vec!["lpush", &format!("channel:{}:recent:{}", channel, nick), &format!("{} {}", now, normalized)]);
+                                        redis_call(db.clone(), vec!["ltrim", &format!("channel:{}:recent:{}", channel, nick), "0", "19"]);
+                                        redis_call(db.clone(), vec!["expire", &format!("channel:{}:recent:{}", channel, nick), &window.to_string()]);
+                                        if count + 1 > limit {
+                                            strike(client.clone(), channel, &nick, message_id.clone(), &msg, "repeats", "1", db.clone());
+                                            struck = true;
+                                        }
+                                    }
+                                }
+                                // short messages are left out so that chat can still spam emotes and reactions together
+                                if copypasta == "true" && !struck && normalized.chars().count() >= 20 {
+                                    let limit: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:copypasta:limit", channel)]).unwrap_or(Value::Data("3".as_bytes().to_owned()))).unwrap();
+                                    let window: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:copypasta:window", channel)]).unwrap_or(Value::Data("30".as_bytes().to_owned()))).unwrap();
+                                    let cutoff: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:copypasta:similarity", channel)]).unwrap_or(Value::Data("80".as_bytes().to_owned()))).unwrap();
+                                    let limit: Result<usize,_> = limit.parse();
+                                    let window: Result<i64,_> = window.parse();
+                                    let cutoff: Result<f32,_> = cutoff.parse();
+                                    if let (Ok(limit), Ok(window), Ok(cutoff)) = (limit, window, cutoff) {
+                                        let recent: Vec<String> = from_redis_value(&redis_call(db.clone(), vec!["lrange", &format!("channel:{}:recent", channel), "0", "-1"]).unwrap_or(Value::Bulk(Vec::new()))).unwrap();
+                                        let mut users: HashSet<String> = HashSet::new();
+                                        for entry in recent.iter() {
+                                            let entry: Vec<&str> = entry.splitn(3, " ").collect();
+                                            if entry.len() > 2 && entry[1] != nick {
+                                                let timestamp: Result<i64,_> = entry[0].parse();
+                                                if let Ok(timestamp) = timestamp {
+                                                    if now - timestamp <= window && similarity(entry[2], &normalized) >= (cutoff / 100.0) { users.insert(entry[1].to_owned()); }
+                                                }
+                                            }
+                                        }
+                                        redis_call(db.clone(), vec!["lpush", &format!("channel:{}:recent", channel), &format!("{} {} {}", now, nick, normalized)]);
+                                        redis_call(db.clone(), vec!["ltrim", &format!("channel:{}:recent", channel), "0", "99"]);
+                                        redis_call(db.clone(), vec!["expire", &format!("channel:{}:recent", channel), &window.to_string()]);
+                                        // like repeats, the limit is how many can post it before the filter kicks in
+                                        if users.len() + 1 > limit {
+                                            strike(client.clone(), channel, &nick, message_id.clone(), &msg, "copypasta", "1", db.clone());
+                                            struck = true;
+                                        }
+                                    }
+                                }
+                            }
+                            let content = normalize_links(&msg);
+                            if (links.len() > 0 || blocked.len() > 0) && !struck && url_regex().is_match(&content) {
+                                let mode: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:links:mode", channel)]).unwrap_or(Value::Data("allow".as_bytes().to_owned()))).unwrap();
                                 let sublinks: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:links:subs", channel)]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
                                 let permitted: Vec<String> = from_redis_value(&redis_call(db.clone(), vec!["keys", &format!("channel:{}:moderation:permitted:*", channel)]).unwrap_or(Value::Bulk(Vec::new()))).unwrap();
                                 let permitted: Vec<String> = permitted.iter().map(|key| { let key: Vec<&str> = key.split(":").collect(); key[4].to_owned() }).collect();
-                                if !(permitted.contains(&nick) || (sublinks == "true" && subscriber)) {
-                                    for word in msg.split_whitespace() {
-                                        if url_regex().is_match(word) {
-                                            let mut url: String = word.to_owned();
-                                            if url.len() > 7 && url.is_char_boundary(7) && &url[..7] != "http://" && &url[..8] != "https://" { url = format!("http://{}", url) }
-                                            match Url::parse(&url) {
-                                                Err(_) => {}
-                                                Ok(url) => {
-                                                    let mut whitelisted = false;
-                                                    for link in &links {
-                                                        let link: Vec<&str> = link.split("/").collect();
-                                                        let mut domain = url.domain().unwrap();
-                                                        if domain.len() > 0 && &domain[..4] == "www." { domain = &domain[4..] }
-                                                        if domain == link[0] {
-                                                            if link.len() > 1 {
-                                                                if url.path().len() > 1 && url.path()[1..] == link[1..].join("/") {
-                                                                    whitelisted = true;
-                                                                    break;
-                                                                }
-                                                            } else {
-                                                                whitelisted = true;
-                                                                break;
-                                                            }
-                                                        }
-                                                    }
-                                                    if !whitelisted {
-                                                        let _ = client.send_privmsg(chan, format!("/timeout {} 1", nick));
-                                                        if display == "true" { send_message(client.clone(), channel.to_owned(), format!("@{} you've been timed out for posting links", nick), db.clone()); }
-                                                    }
+                                let exempt = permitted.contains(&nick) || (sublinks == "true" && subscriber);
+                                for word in content.split_whitespace() {
+                                    if url_regex().is_match(word) {
+                                        let mut url: String = word.to_owned();
+                                        if !url.starts_with("http://") && !url.starts_with("https://") { url = format!("http://{}", url) }
+                                        match Url::parse(&url) {
+                                            Err(_) => {}
+                                            Ok(url) => {
+                                                // blocked links are removed even for subs and permitted users
+                                                let mut remove = blocked.iter().any(|link| link_matches(link, &url));
+                                                if !remove && !exempt && mode != "block" && links.len() > 0 {
+                                                    remove = !links.iter().any(|link| link_matches(link, &url));
+                                                }
+                                                if remove {
+                                                    strike(client.clone(), channel, &nick, message_id.clone(), &msg, "links", "1", db.clone());
+                                                    struck = true;
+                                                    break;
                                                 }
                                             }
                                         }
@@ -314,6 +524,7 @@ fn register_handler(bot: String, client: IrcClient, reactor: &mut IrcReactor, db
                                 }
                             }
                             for key in bkeys {
+                                if struck { break }
                                 let key: Vec<&str> = key.split(":").collect();
                                 let rgx: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:moderation:blacklist:{}", channel, key[4]), "regex"]).expect(&format!("channel:{}:moderation:blacklist:{}", channel, key[4]))).unwrap();
                                 let length: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:moderation:blacklist:{}", channel, key[4]), "length"]).expect(&format!("channel:{}:moderation:blacklist:{}", channel, key[4]))).unwrap();
@@ -321,13 +532,19 @@ fn register_handler(bot: String, client: IrcClient, reactor: &mut IrcReactor, db
                                     Err(e) => { log_error(Some(Right(vec![&channel])), "regex_error", &e.to_string(), db.clone()) }
                                     Ok(rgx) => {
                                         if rgx.is_match(&msg) {
-                                            let _ = client.send_privmsg(chan, format!("/timeout {} {}", nick, length));
-                                            if display == "true" { send_message(client.clone(), channel.to_owned(), format!("@{} you've been timed out for posting a blacklisted phrase", nick), db.clone()); }
+                                            strike(client.clone(), channel, &nick, message_id.clone(), &msg, "blacklist", &length, db.clone());
+                                            struck = true;
                                             break;
                                         }
                                     }
                                 }
                             }
+                            // the account age may need a request to twitch, so it's checked last and only when nothing else struck
+                            if let Ok(value) = age {
+                                let age: String = from_redis_value(&value).unwrap();
+                                let res: Result<i64,_> = age.parse();
+                                if let Ok(age) = res { if !struck { spawn_age_check(client.clone(), db.clone(), channel.to_string(), nick.clone(), message_id.clone(), msg.to_string(), age); } }
+                            }
                         }
 
                         // expand aliases
@@ -347,11 +564,10 @@ fn register_handler(bot: String, client: IrcClient, reactor: &mut IrcReactor, db
                         // parse native commands
                         for cmd in commands::native_commands.iter() {
                             if format!("{}{}", prefix, cmd.0) == word {
-                                if args.len() == 0 {
-                                    if !cmd.2 || auth { (cmd.1)(client.clone(), channel.to_owned(), args.clone(), Some(irc_message.clone()), db.clone()) }
-                                } else {
-                                    if !cmd.3 || auth { (cmd.1)(client.clone(), channel.to_owned(), args.clone(), Some(irc_message.clone()), db.clone()) }
-                                }
+                                let mut default = Role::Everyone;
+                                if (args.len() == 0 && cmd.2) || (args.len() > 0 && cmd.3) { default = Role::Moderator }
+                                let required = get_required_role(channel, cmd.0, &args, default, db.clone());
+                                if role >= required { (cmd.1)(client.clone(), channel.to_owned(), args.clone(), Some(irc_message.clone()), db.clone()) }
                                 break;
                             }
                         }
@@ -360,20 +576,55 @@ fn register_handler(bot: String, client: IrcClient, reactor: &mut IrcReactor, db
                         let res: Result<Value,_> = redis_call(db.clone(), vec!["hget", &format!("channel:{}:commands:{}", channel.to_owned(), word), "message"]);
                         if let Ok(value) = res {
                             let message: String = from_redis_value(&value).unwrap();
-                            let res: Result<Value,_> = redis_call(db.clone(), vec!["hget", &format!("channel:{}:commands:{}", channel.to_owned(), word), "lastrun"]);
-                            let mut within5 = false;
-                            if let Ok(value) = res {
-                                let lastrun: String = from_redis_value(&value).unwrap();
-                                let timestamp = DateTime::parse_from_rfc3339(&lastrun).unwrap();
-                                let diff = Utc::now().signed_duration_since(timestamp);
-                                if diff.num_seconds() < 5 { within5 = true }
-                            }
-                            if !within5 {
-                                let mut protected: &str = "cmd";
-                                if args.len() > 0 { protected = "arg" }
-                                let protected: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:commands:{}", channel, word), &format!("{}_protected", protected)]).expect(&format!("{}:{}", &format!("channel:{}:commands:{}", channel, word), &format!("{}_protected", protected)))).unwrap();
-                                if protected == "false" || auth {
+                            let mut protected: &str = "cmd";
+                            if args.len() > 0 { protected = "arg" }
+                            let protected: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:commands:{}", channel, word), &format!("{}_protected", protected)]).expect(&format!("{}:{}", &format!("channel:{}:commands:{}", channel, word), &format!("{}_protected", protected)))).unwrap();
+                            let mut default = Role::Everyone;
+                            if protected == "true" { default = Role::Moderator }
+                            let required = get_required_role(channel, &word, &args, default, db.clone());
+                            if role >= required {
+                                let cooldown: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:commands:{}", channel, word), "cooldown"]).unwrap_or(Value::Data("5".as_bytes().to_owned()))).unwrap();
+                                let user_cooldown: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:commands:{}", channel, word), "user_cooldown"]).unwrap_or(Value::Data("0".as_bytes().to_owned()))).unwrap();
+                                let cooldown: i64 = cooldown.parse().unwrap_or(5);
+                                let user_cooldown: i64 = user_cooldown.parse().unwrap_or(0);
+
+                                // moderators bypass both cooldowns
+                                let mut remaining: i64 = 0;
+                                if !auth {
+                                    let res: Result<Value,_> = redis_call(db.clone(), vec!["hget", &format!("channel:{}:commands:{}", channel, word), "lastrun"]);
+                                    if let Ok(value) = res {
+                                        let lastrun: String = from_redis_value(&value).unwrap();
+                                        let timestamp = DateTime::parse_from_rfc3339(&lastrun).unwrap();
+                                        let diff = Utc::now().signed_duration_since(timestamp);
+                                        if diff.num_seconds() < cooldown { remaining = cooldown - diff.num_seconds() }
+                                    }
+                                    if user_cooldown > 0 {
+                                        let res: Result<Value,_> = redis_call(db.clone(), vec!["ttl", &format!("channel:{}:cooldowns:{}:{}", channel, word, nick)]);
+                                        if let Ok(value) = res {
+                                            let ttl: i64 = from_redis_value(&value).unwrap_or(0);
+                                            if ttl > remaining { remaining = ttl }
+                                        }
+                                    }
+                                }
+
+                                if remaining > 0 {
+                                    // the cooldown message is sent at most once per cooldown so that spamming the command can't flood chat
+                                    let res: Result<Value,_> = redis_call(db.clone(), vec!["hget", &format!("channel:{}:commands:{}", channel, word), "cooldown_message"]);
+                                    if let Ok(value) = res {
+                                        // the cooldown message is sent at most once per cooldown so that spamming the command can't flood chat
+                                        let notify: Result<Value,_> = redis_call(db.clone(), vec!["set", &format!("channel:{}:cooldowns:notified:{}", channel, word), "true", "NX", "EX", &remaining.to_string()]);
+                                        if let Ok(_value) = notify {
+                                            let cmessage: String = from_redis_value(&value).unwrap();
+                                            let cmessage = replace_var("remaining", &remaining.to_string(), &cmessage);
+                                            send_parsed_message(client.clone(), channel.to_owned(), cmessage, Vec::new(), Some(irc_message.clone()), db.clone(), false);
+                                        }
+                                    }
+                                } else {
                                     redis_call(db.clone(), vec!["hset", &format!("channel:{}:commands:{}", channel, word), "lastrun", &Utc::now().to_rfc3339()]);
+                                    if user_cooldown > 0 {
+                                        redis_call(db.clone(), vec!["set", &format!("channel:{}:cooldowns:{}:{}", channel, word, nick), ""]);
+                                        redis_call(db.clone(), vec!["expire", &format!("channel:{}:cooldowns:{}:{}", channel, word, nick), &user_cooldown.to_string()]);
+                                    }
                                     send_parsed_message(client.clone(), channel.to_owned(), message.to_owned(), args.clone(), Some(irc_message.clone()), db.clone(), false);
                                 }
                             }
@@ -399,19 +650,54 @@ fn register_handler(bot: String, client: IrcClient, reactor: &mut IrcReactor, db
                             }
                         }
 
+                        // parse giveaway entries and claims, unless moderation just removed the message
+                        if !struck { run_giveaway(client.clone(), channel.to_owned(), irc_message.clone(), &msg, subscriber, db.clone()); }
+
+                        // parse poll votes typed as the option itself
+                        if !struck { commands::cast_vote(channel, &nick, &msg, false, db.clone()); }
+
                         // parse keywords
                         let keys: Vec<String> = from_redis_value(&redis_call(db.clone(), vec!["keys", &format!("channel:{}:keywords:*", channel)]).unwrap_or(Value::Bulk(Vec::new()))).unwrap();
                         for key in keys.iter() {
                             let key: Vec<&str> = key.split(":").collect();
                             let regex: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:keywords:{}", channel, key[3]), "regex"]).expect(&format!("channel:{}:keywords:{}", channel, key[3]))).unwrap();
-                            let cmd: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:keywords:{}", channel, key[3]), "cmd"]).expect(&format!("channel:{}:keywords:{}", channel, key[3]))).unwrap();
-                            let res = Regex::new(&regex);
+                            let sensitive: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:keywords:{}", channel, key[3]), "sensitive"]).unwrap_or(Value::Data("true".as_bytes().to_owned()))).unwrap();
+                            let exempt: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:keywords:{}", channel, key[3]), "exempt"]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
+                            if exempt == "true" && auth { continue }
+                            let res = RegexBuilder::new(&regex).case_insensitive(sensitive != "true").build();
                             if let Ok(rgx) = res {
-                                if rgx.is_match(&msg) {
-                                    let res: Result<Value,_> = redis_call(db.clone(), vec!["hget", &format!("channel:{}:commands:{}", channel, cmd), "message"]);
+                                if let Some(captures) = rgx.captures(&msg) {
+                                    let cooldown: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:keywords:{}", channel, key[3]), "cooldown"]).unwrap_or(Value::Data("0".as_bytes().to_owned()))).unwrap();
+                                    let cooldown: i64 = cooldown.parse().unwrap_or(0);
+                                    let res: Result<Value,_> = redis_call(db.clone(), vec!["hget", &format!("channel:{}:keywords:{}", channel, key[3]), "lastrun"]);
                                     if let Ok(value) = res {
-                                        let message: String = from_redis_value(&value).unwrap();
-                                        send_parsed_message(client.clone(), channel.to_owned(), message, Vec::new(), None, db.clone(), false);
+                                        let lastrun: String = from_redis_value(&value).unwrap();
+                                        let timestamp = DateTime::parse_from_rfc3339(&lastrun).unwrap();
+                                        let diff = Utc::now().signed_duration_since(timestamp);
+                                        if diff.num_seconds() < cooldown { continue }
+                                    }
+
+                                    // a keyword either carries its own response or points to a custom command
+                                    let mut message: Option<String> = None;
+                                    let res: Result<Value,_> = redis_call(db.clone(), vec!["hget", &format!("channel:{}:keywords:{}", channel, key[3]), "message"]);
+                                    if let Ok(value) = res {
+                                        let response: String = from_redis_value(&value).unwrap();
+                                        if !response.is_empty() { message = Some(response) }
+                                    }
+                                    if message.is_none() {
+                                        let cmd: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:keywords:{}", channel, key[3]), "cmd"]).unwrap_or(Value::Data("".as_bytes().to_owned()))).unwrap();
+                                        let res: Result<Value,_> = redis_call(db.clone(), vec!["hget", &format!("channel:{}:commands:{}", channel, cmd), "message"]);
+                                        if let Ok(value) = res { message = Some(from_redis_value(&value).unwrap()) }
+                                    }
+
+                                    if let Some(mut message) = message {
+                                        // strip parentheses so chatters can't inject variables through a capture
+                                        for i in 0..captures.len() {
+                                            let capture = captures.get(i).map_or("", |m| m.as_str()).replace("(", "").replace(")", "");
+                                            message = replace_var(&format!("match {}", i), &capture, &message);
+                                        }
+                                        redis_call(db.clone(), vec!["hset", &format!("channel:{}:keywords:{}", channel, key[3]), "lastrun", &Utc::now().to_rfc3339()]);
+                                        send_parsed_message(client.clone(), channel.to_owned(), message, Vec::new(), Some(irc_message.clone()), db.clone(), false);
                                     }
                                     break;
                                 }
@@ -434,7 +720,7 @@ fn start_rocket() {
     thread::spawn(move || {
         rocket::ignite()
           .mount("/assets", StaticFiles::from("assets"))
-          .mount("/", routes![web::index, web::dashboard, web::commands, web::patreon_cb, web::patreon_refresh, web::spotify_cb, web::twitch_cb, web::public_data, web::data, web::logs, web::local, web::login, web::logout, web::signup, web::password, web::title, web::game, web::new_command, web::save_command, web::trash_command, web::new_notice, web::trash_notice, web::save_setting, web::trash_setting, web::new_blacklist, web::save_blacklist, web::trash_blacklist, web::new_keyword, web::save_keyword, web::trash_keyword, web::trash_song])
+          .mount("/", routes![web::index, web::dashboard, web::commands, web::patreon_cb, web::patreon_refresh, web::spotify_cb, web::twitch_cb, web::public_data, web::data, web::logs, web::moderation_log, web::local, web::login, web::logout, web::signup, web::password, web::title, web::game, web::new_command, web::save_command, web::trash_command, web::save_permission, web::trash_permission, web::new_notice, web::trash_notice, web::save_setting, web::trash_setting, web::new_blacklist, web::save_blacklist, web::trash_blacklist, web::save_filter, web::trash_filter, web::save_event, web::trash_event, web::save_greeting, web::trash_greeting, web::save_counter, web::trash_counter, web::new_quote, web::save_quote, web::trash_quote, web::poll, web::new_keyword, web::save_keyword, web::trash_keyword, web::trash_song])
           .register(catchers![web::internal_error, web::not_found])
           .attach(Template::fairing())
           .attach(RedisConnection::fairing())
@@ -475,6 +761,61 @@ fn redis_listener(receiver: Receiver<Vec<String>>, sender: Sender<Result<Value,
     });
 }
 
+// sends queued messages for a bot while staying under twitch's rate limits, 100 messages per 30 seconds
+// in channels where the bot is a moderator and 20 per 30 seconds elsewhere
+fn message_queue(bot: String, client: Arc<IrcClient>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>), receiver: Receiver<ThreadAction>) {
+    thread::spawn(move || {
+        let mut tokens: f64 = 100.0;
+        let mut user_tokens: f64 = 20.0;
+        let mut last = time::Instant::now();
+        loop {
+            let rsp = receiver.recv_timeout(time::Duration::from_millis(100));
+            match rsp {
+                Ok(action) => {
+                    match action {
+                        ThreadAction::Kill => break,
+                        ThreadAction::Part(_) => {}
+                    }
+                }
+                Err(err) => {
+                    match err {
+                        RecvTimeoutError::Disconnected => break,
+                        RecvTimeoutError::Timeout => {}
+                    }
+                }
+            }
+
+            let elapsed = last.elapsed().as_millis() as f64 / 1000.0;
+            last = time::Instant::now();
+            tokens = (tokens + elapsed * 100.0 / 30.0).min(100.0);
+            user_tokens = (user_tokens + elapsed * 20.0 / 30.0).min(20.0);
+
+            while tokens >= 1.0 {
+                let res: Result<Value,_> = redis_call(db.clone(), vec!["rpop", &format!("bot:{}:queue", bot)]);
+                match res {
+                    Err(_) => break,
+                    Ok(value) => {
+                        let entry: String = from_redis_value(&value).unwrap();
+                        let mut parts = entry.splitn(2, ' ');
+                        if let (Some(channel), Some(message)) = (parts.next(), parts.next()) {
+                            let auth: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:auth", channel)]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
+                            if auth != "true" {
+                                if user_tokens < 1.0 {
+                                    redis_call(db.clone(), vec!["rpush", &format!("bot:{}:queue", bot), &entry]);
+                                    break;
+                                }
+                                user_tokens -= 1.0;
+                            }
+                            tokens -= 1.0;
+                            let _ = client.send_privmsg(format!("#{}", channel), message);
+                        }
+                    }
+                }
+            }
+        }
+    });
+}
+
 fn client_listener(client: Arc<IrcClient>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>), receiver: Receiver<ClientAction>) {
     thread::spawn(move || {
         let con = acquire_con();
@@ -485,7 +826,7 @@ fn client_listener(client: Arc<IrcClient>, db: (Sender<Vec<String>>, Receiver<Re
                 Ok(action) => {
                     match action {
                         ClientAction::Privmsg(channel, message) => {
-                            let _ = client.send_privmsg(format!("#{}", &channel), &message);
+                            queue_privmsg(&client, &channel, &message, db.clone());
                         }
                         ClientAction::Part(channel) => {
                             let _ = client.send_part(format!("#{}", &channel));
@@ -903,129 +1244,125 @@ fn run_scheduled_notices(db: (Sender<Vec<String>>, Receiver<Result<Value, String
     });
 }
 
-// TODO: reimplement shoutouts
-/*
-fn run_shoutouts(db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) {
-    thread::spawn(move || {
-        loop {
-            let channels: HashSet<String> = from_redis_value(&redis_call(db.clone(), vec!["smembers", "channels"]).unwrap_or(Value::Bulk(Vec::new()))).unwrap();
-            for channel in channels {
+fn run_shoutout(client: Arc<IrcClient>, channel: String, irc_message: Message, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) {
+    let message: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:settings", channel), "channel:raid-message"]).unwrap_or_else(|_| redis_call(db.clone(), vec!["hget", &format!("channel:{}:settings", channel), "channel:host-message"]).unwrap_or(Value::Data("".as_bytes().to_owned())))).unwrap();
+    let live: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:live", channel)]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
+    if live == "true" && !message.is_empty() {
+        let id = get_tag(&irc_message, "user-id").unwrap_or("".to_owned());
+        let login = get_tag(&irc_message, "msg-param-login").unwrap_or("".to_owned());
+        let name = get_tag(&irc_message, "msg-param-displayName").unwrap_or(login.clone());
+        let viewers = get_tag(&irc_message, "msg-param-viewerCount").unwrap_or("0".to_owned());
+
+        let minimum: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:settings", channel), "channel:raid-minimum"]).unwrap_or(Value::Data("0".as_bytes().to_owned()))).unwrap();
+        let minimum: u32 = minimum.parse().unwrap_or(0);
+        let count: u32 = viewers.parse().unwrap_or(0);
+        let list: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:settings", channel), "autohost:blacklist"]).unwrap_or(Value::Data("".as_bytes().to_owned()))).unwrap();
+        let blacklist: Vec<String> = list.split_whitespace().map(|nick| nick.to_lowercase()).collect();
+
+        if !id.is_empty() && count >= minimum && !blacklist.contains(&login.to_lowercase()) && !blacklist.contains(&id) {
+            // only shout out each raider once per stream
+            let added: u16 = from_redis_value(&redis_call(db.clone(), vec!["sadd", &format!("channel:{}:hosts:recent", channel), &id]).unwrap_or(Value::Int(0))).unwrap();
+            if added == 0 { return }
+            thread::spawn(move || {
+                let token: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:token", &channel)]).expect(&format!("channel:{}:token", &channel))).unwrap();
                 let channelC = channel.clone();
-                let live: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:live", channel)]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
-                let hostm: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:live", channel)]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
-                let autom: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:live", channel)]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
-                let hostm: String = con.hget(format!("channel:{}:settings", &so_channel), "channel:host-message").unwrap_or("".to_owned());
-                let autom: String = con.hget(format!("channel:{}:settings", &so_channel), "channel:autohost-message").unwrap_or("".to_owned());
-                if live == "true" && (!hostm.is_empty() || !autom.is_empty()) {
-                    let id: String = con.get(format!("channel:{}:id", &so_channel)).unwrap();
-                    let recent: Vec<String> = con.smembers(format!("channel:{}:hosts:recent", &so_channel)).unwrap_or(Vec::new());
-                    let token: String = redis_string_async(vec!["get", &format!("channel:{}:token", &so_channel)]).wait().unwrap().unwrap();
-                    let future = twitch_kraken_request(token, None, None, Method::GET, &format!("https://api.twitch.tv/kraken/channels/{}/hosts", &id)).send()
+                let dbC = db.clone();
+                let future = twitch_kraken_request(token, None, None, Method::GET, &format!("https://api.twitch.tv/kraken/channels/{}", &id)).send()
+                    .and_then(|mut res| { mem::replace(res.body_mut(), Decoder::empty()).concat2() })
+                    .map_err(|e| println!("request error: {}", e))
+                    .map(move |body| {
+                        let body = std::str::from_utf8(&body).unwrap().to_string();
+                        let json: Result<KrakenChannel,_> = serde_json::from_str(&body);
+                        match json {
+                            Err(e) => {
+                                log_error(Some(Right(vec![&channelC])), "run_shoutout", &e.to_string(), dbC.clone());
+                                log_error(Some(Right(vec![&channelC])), "request_body", &body, dbC.clone());
+                                None
+                            }
+                            Ok(json) => Some(json)
+                        }
+                    });
+                let mut rt = Runtime::new().expect("runtime:new");
+                let (game, url) = match rt.block_on(future) {
+                    Ok(Some(json)) => (json.game, json.url),
+                    _ => ("".to_owned(), format!("https://twitch.tv/{}", login))
+                };
+                rt.shutdown_now();
+
+                let mut message = message;
+                message = replace_var("url", &url, &message);
+                message = replace_var("name", &name, &message);
+                message = replace_var("game", &game, &message);
+                message = replace_var("viewers", &viewers, &message);
+                send_parsed_message(client, channel.clone(), message, Vec::new(), Some(irc_message), db.clone(), true);
+            });
+        }
+    }
+}
+
+fn run_giveaway(client: Arc<IrcClient>, channel: String, irc_message: Message, msg: &str, subscriber: bool, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) {
+    let nick = get_nick(&irc_message);
+
+    // the winner of the last draw claims the prize by chatting before the claim timer runs out
+    let res: Result<Value,_> = redis_call(db.clone(), vec!["get", &format!("channel:{}:giveaway:winner", channel)]);
+    if let Ok(value) = res {
+        let winner: String = from_redis_value(&value).unwrap();
+        let winner: Vec<&str> = winner.split_whitespace().collect();
+        if winner.len() > 1 && winner[0] == nick {
+            redis_call(db.clone(), vec!["del", &format!("channel:{}:giveaway:winner", channel)]);
+            redis_call(db.clone(), vec!["hset", &format!("channel:{}:giveaways:{}", channel, winner[1]), "claimed", "true"]);
+            send_message(client.clone(), channel.to_owned(), format!("{} has claimed the prize!", &nick), db.clone());
+        }
+    }
+
+    let res: Result<Value,_> = redis_call(db.clone(), vec!["get", &format!("channel:{}:giveaway:keyword", channel)]);
+    if let Ok(value) = res {
+        let keyword: String = from_redis_value(&value).unwrap();
+        if msg.split_whitespace().next().map_or(true, |word| word.to_lowercase() != keyword.to_lowercase()) { return }
+        let entered: bool = from_redis_value(&redis_call(db.clone(), vec!["hexists", &format!("channel:{}:giveaway:entries", channel), &nick]).unwrap_or(Value::Int(0))).unwrap_or(false);
+        let drawn: bool = from_redis_value(&redis_call(db.clone(), vec!["sismember", &format!("channel:{}:giveaway:drawn", channel), &nick]).unwrap_or(Value::Int(0))).unwrap_or(false);
+        if entered || drawn { return }
+
+        let followers: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:settings", channel), "giveaway:followers"]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
+        let subs: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:settings", channel), "giveaway:subs"]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
+        let watchtime: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:settings", channel), "giveaway:watchtime"]).unwrap_or(Value::Data("0".as_bytes().to_owned()))).unwrap();
+        let tickets: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:settings", channel), "giveaway:sub-tickets"]).unwrap_or(Value::Data("1".as_bytes().to_owned()))).unwrap();
+        let watchtime: i64 = watchtime.parse().unwrap_or(0);
+
+        if subs == "true" && !subscriber { return }
+        if watchtime > 0 {
+            let watched: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:watchtimes", channel), &nick]).unwrap_or(Value::Data("0".as_bytes().to_owned()))).unwrap();
+            let watched: i64 = watched.parse().unwrap_or(0);
+            if watched < watchtime { return }
+        }
+        let tickets = if subscriber { tickets.parse::<u32>().unwrap_or(1).max(1) } else { 1 };
+
+        if followers == "true" {
+            if let Some(user_id) = get_id(&irc_message) {
+                thread::spawn(move || {
+                    let id: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:id", channel)]).expect(&format!("channel:{}:id", channel))).unwrap();
+                    let token: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:token", &channel)]).expect(&format!("channel:{}:token", &channel))).unwrap();
+                    let future = twitch_kraken_request(token, None, None, Method::GET, &format!("https://api.twitch.tv/kraken/users/{}/follows/channels/{}", &user_id, &id)).send()
                         .and_then(|mut res| { mem::replace(res.body_mut(), Decoder::empty()).concat2() })
                         .map_err(|e| println!("request error: {}", e))
                         .map(move |body| {
-                            let con = Arc::new(acquire_con());
-                            let body = std::str::from_utf8(&body).unwrap();
-                            let json: Result<KrakenHosts,_> = serde_json::from_str(&body);
-                            match json {
-                                Err(e) => {
-                                    log_error(Some(Right(vec![&channelC])), "auto_shoutouts", &e.to_string(), db.clone());
-                                    log_error(Some(Right(vec![&channelC])), "request_body", &body, db.clone());
-                                }
-                                Ok(json) => {
-                                    let list: String = con.hget(format!("channel:{}:settings", &channelC), "autohost:blacklist").unwrap_or("".to_owned()); // UNDOCUMENTED
-                                    let mut blacklist: Vec<String> = Vec::new();
-                                    for nick in list.split_whitespace() { blacklist.push(nick.to_string()) }
-                                    for host in json.hosts {
-                                        let client = clientC.clone();
-                                        let channel = channelC.clone();
-                                        let blacklist = blacklist.clone();
-                                        let hostm = hostm.clone();
-                                        let autom = autom.clone();
-                                        if !recent.contains(&host.host_id) {
-                                            let _: () = con.sadd(format!("channel:{}:hosts:recent", &channel), &host.host_id).unwrap();
-                                            let token: String = redis_string_async(vec!["get", &format!("channel:{}:token", &channel)]).wait().unwrap().unwrap();
-                                            let future = twitch_kraken_request(token, None, None, Method::GET, &format!("https://api.twitch.tv/kraken/streams?channel={}", &host.host_id)).send()
-                                                .and_then(|mut res| { mem::replace(res.body_mut(), Decoder::empty()).concat2() })
-                                                .map_err(|e| println!("request error: {}", e))
-                                                .map(move |body| {
-                                                    let con = Arc::new(acquire_con());
-                                                    let body = std::str::from_utf8(&body).unwrap();
-                                                    let json: Result<KrakenStreams,_> = serde_json::from_str(&body);
-                                                    match json {
-                                                        Err(e) => {
-                                                            log_error(Some(Right(vec![&channel])), "auto_shoutouts", &e.to_string(), db.clone());
-                                                            log_error(Some(Right(vec![&channel])), "request_body", &body, db.clone());
-                                                        }
-                                                        Ok(json) => {
-                                                            if !blacklist.contains(&host.host_id) {
-                                                                if json.total > 0 {
-                                                                    if !hostm.is_empty() {
-                                                                        let mut message: String = hostm.to_owned();
-                                                                        message = replace_var("url", &json.streams[0].channel.url, &message);
-                                                                        message = replace_var("name", &json.streams[0].channel.display_name, &message);
-                                                                        message = replace_var("game", &json.streams[0].channel.game, &message);
-                                                                        message = replace_var("viewers", &json.streams[0].viewers.to_string(), &message);
-                                                                        send_message(client.clone(), channel.clone(), message, db.clone());
-                                                                    }
-                                                                } else {
-                                                                    if !autom.is_empty() {
-                                                                        let token: String = redis_string_async(vec!["get", &format!("channel:{}:token", &channel)]).wait().unwrap().unwrap();
-                                                                        let future = twitch_kraken_request(token, None, None, Method::GET, &format!("https://api.twitch.tv/kraken/channels/{}", &host.host_id)).send()
-                                                                            .and_then(|mut res| { mem::replace(res.body_mut(), Decoder::empty()).concat2() })
-                                                                            .map_err(|e| println!("request error: {}", e))
-                                                                            .map(move |body| {
-                                                                                let con = Arc::new(acquire_con());
-                                                                                let body = std::str::from_utf8(&body).unwrap();
-                                                                                let json: Result<KrakenChannel,_> = serde_json::from_str(&body);
-                                                                                match json {
-                                                                                    Err(e) => {
-                                                                                        log_error(Some(Right(vec![&channel])), "auto_shoutouts", &e.to_string(), db.clone());
-                                                                                        log_error(Some(Right(vec![&channel])), "request_body", &body, db.clone());
-                                                                                    }
-                                                                                    Ok(json) => {
-                                                                                        let mut message: String = autom.to_owned();
-                                                                                        message = replace_var("url", &json.url, &message);
-                                                                                        message = replace_var("name", &json.display_name, &message);
-                                                                                        message = replace_var("game", &json.game, &message);
-                                                                                        send_message(client.clone(), channel.clone(), message, db.clone());
-                                                                                    }
-                                                                                }
-                                                                            });
-                                                                        thread::spawn(move || { tokio::run(future) });
-                                                                    }
-                                                                }
-                                                            }
-                                                        }
-                                                    }
-                                                });
-                                            thread::spawn(move || { tokio::run(future) });
-                                        }
-                                    }
-                                }
-                            }
+                            let body = std::str::from_utf8(&body).unwrap().to_string();
+                            let json: Result<KrakenFollow,_> = serde_json::from_str(&body);
+                            json.is_ok()
                         });
-                    thread::spawn(move || { tokio::run(future) });
-                }
-                let rsp = so_receiver.recv_timeout(time::Duration::from_secs(60));
-                match rsp {
-                    Ok(action) => {
-                        match action {
-                            ThreadAction::Kill => break,
-                            ThreadAction::Part(_) => {}
-                        }
-                    }
-                    Err(err) => {
-                        match err {
-                            RecvTimeoutError::Disconnected => break,
-                            RecvTimeoutError::Timeout => {}
-                        }
+                    let mut rt = Runtime::new().expect("runtime:new");
+                    // twitch responds with an error instead of a follow when the user isn't following
+                    if let Ok(true) = rt.block_on(future) {
+                        redis_call(db.clone(), vec!["hset", &format!("channel:{}:giveaway:entries", channel), &nick, &tickets.to_string()]);
                     }
-                }
+                    rt.shutdown_now();
+                });
             }
+        } else {
+            redis_call(db.clone(), vec!["hset", &format!("channel:{}:giveaway:entries", channel), &nick, &tickets.to_string()]);
         }
-    });
-}*/
+    }
+}
 
 fn run_commercials(db: (Sender<Vec<String>>, Receiver<Result<Value, String>>), channel: String, client: Sender<ClientAction>, receiver: Receiver<ThreadAction>) {
     thread::spawn(move || {
@@ -1473,7 +1810,15 @@ fn update_watchtime(db: (Sender<Vec<String>>, Receiver<Result<Value, String>>))
                 let db = db.clone();
                 let live: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:live", &channel)]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
                 let enabled: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:settings", &channel), "channel:viewerstats"]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
-                if live == "true" && enabled == "true" {
+                let points: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:settings", &channel), "points:enabled"]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
+                let per_minute: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:settings", &channel), "points:per-minute"]).unwrap_or(Value::Data("1".as_bytes().to_owned()))).unwrap();
+                let sub_bonus: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:settings", &channel), "points:sub-bonus"]).unwrap_or(Value::Data("0".as_bytes().to_owned()))).unwrap();
+                let active_bonus: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:settings", &channel), "points:active-bonus"]).unwrap_or(Value::Data("0".as_bytes().to_owned()))).unwrap();
+                let per_minute: i64 = per_minute.parse().unwrap_or(1);
+                let sub_bonus: i64 = sub_bonus.parse().unwrap_or(0);
+                let active_bonus: i64 = active_bonus.parse().unwrap_or(0);
+                let blacklist: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:settings", &channel), "watchtime:blacklist"]).unwrap_or(Value::Data("".as_bytes().to_owned()))).unwrap();
+                if live == "true" && (enabled == "true" || points == "true") {
                     let future = request(Method::GET, None, &format!("http://tmi.twitch.tv/group/user/{}/chatters", &channel)).send()
                         .and_then(|mut res| { mem::replace(res.body_mut(), Decoder::empty()).concat2() })
                         .map_err(|e| println!("request error: {}", e))
@@ -1494,13 +1839,25 @@ fn update_watchtime(db: (Sender<Vec<String>>, Receiver<Result<Value, String>>))
                                     nicks.append(&mut viewers);
                                     nicks.append(&mut vips);
                                     for nick in nicks.iter() {
-                                        let res: Result<Value,_> = redis_call(db.clone(), vec!["hget", &format!("channel:{}:watchtimes", &channel), &nick]);
-                                        if let Ok(value) = res {
-                                            let wt: String = from_redis_value(&value).unwrap();
-                                            let num: i64 = wt.parse().unwrap();
-                                            redis_call(db.clone(), vec!["hset", &format!("channel:{}:watchtimes", &channel), &nick, (num + 1).to_string().as_ref()]);
-                                        } else {
-                                            redis_call(db.clone(), vec!["hset", &format!("channel:{}:watchtimes", &channel), &nick, "1"]);
+                                        if enabled == "true" {
+                                            let res: Result<Value,_> = redis_call(db.clone(), vec!["hget", &format!("channel:{}:watchtimes", &channel), &nick]);
+                                            if let Ok(value) = res {
+                                                let wt: String = from_redis_value(&value).unwrap();
+                                                let num: i64 = wt.parse().unwrap();
+                                                redis_call(db.clone(), vec!["hset", &format!("channel:{}:watchtimes", &channel), &nick, (num + 1).to_string().as_ref()]);
+                                            } else {
+                                                redis_call(db.clone(), vec!["hset", &format!("channel:{}:watchtimes", &channel), &nick, "1"]);
+                                            }
+                                        }
+                                        if points == "true" && !blacklist.split_whitespace().any(|name| name.to_lowercase() == *nick) {
+                                            let mut amount = per_minute;
+                                            let sub: bool = from_redis_value(&redis_call(db.clone(), vec!["sismember", &format!("channel:{}:points:subs", &channel), &nick]).unwrap_or(Value::Int(0))).unwrap_or(false);
+                                            let active: bool = from_redis_value(&redis_call(db.clone(), vec!["exists", &format!("channel:{}:points:active:{}", &channel, &nick)]).unwrap_or(Value::Int(0))).unwrap_or(false);
+                                            if sub { amount += sub_bonus }
+                                            if active { amount += active_bonus }
+                                            if amount != 0 {
+                                                redis_call(db.clone(), vec!["hincrby", &format!("channel:{}:points", &channel), &nick, &amount.to_string()]);
+                                            }
                                         }
                                     }
                                 }
@@ -0,0 +1,339 @@
+use crate::commands::*;
+use std::mem;
+use std::sync::Arc;
+use crossbeam_channel::{Sender,Receiver};
+use reqwest::r#async::Decoder;
+use futures::stream::Stream;
+use futures::future::Future;
+use tokio::runtime::Runtime;
+use irc::client::prelude::*;
+use redis::Value;
+
+// a parsed command template. each argument of a variable is its own list of nodes so that
+// variables can nest, e.g. (counter (args 1))
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Text(String),
+    Var(String, Vec<Arg>)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arg {
+    pub nodes: Vec<Node>,
+    pub quoted: bool
+}
+
+// constructs that are handled by the evaluator itself rather than by a variable function
+const KEYWORDS: [&str; 2] = ["if", "else"];
+const OPERATORS: [&str; 7] = ["==", "!=", ">", "<", ">=", "<=", "contains"];
+
+pub fn is_var(name: &str) -> bool {
+    KEYWORDS.contains(&name) || command_vars.iter().any(|var| var.0 == name) || command_vars_async.iter().any(|var| var.0 == name)
+}
+
+pub fn parse(template: &str) -> Vec<Node> {
+    let chars: Vec<char> = template.chars().collect();
+    let mut pos = 0;
+    let mut nodes = Vec::new();
+    let mut text = String::new();
+    while pos < chars.len() {
+        match chars[pos] {
+            '\\' if pos + 1 < chars.len() && is_escapable(chars[pos+1]) => {
+                text.push(chars[pos+1]);
+                pos += 2;
+            }
+            '(' => {
+                match parse_var(&chars, &mut pos) {
+                    Some(node) => {
+                        if !text.is_empty() { nodes.push(Node::Text(mem::replace(&mut text, String::new()))) }
+                        nodes.push(node);
+                    }
+                    None => {
+                        text.push('(');
+                        pos += 1;
+                    }
+                }
+            }
+            c => {
+                text.push(c);
+                pos += 1;
+            }
+        }
+    }
+    if !text.is_empty() { nodes.push(Node::Text(text)) }
+    return nodes;
+}
+
+fn is_escapable(c: char) -> bool {
+    c == '(' || c == ')' || c == '"' || c == '\\'
+}
+
+// parses a variable starting at the opening paren. text in parens that isn't a known variable is
+// left alone, so this returns None and the caller treats the paren as plain text
+fn parse_var(chars: &[char], pos: &mut usize) -> Option<Node> {
+    let mut i = *pos + 1;
+    let mut name = String::new();
+    while i < chars.len() && !chars[i].is_whitespace() && chars[i] != '(' && chars[i] != ')' {
+        name.push(chars[i]);
+        i += 1;
+    }
+    if !is_var(&name) { return None }
+
+    let mut args = Vec::new();
+    // plain parens inside the arguments are kept as text as long as they're balanced, e.g. (math ((counter deaths) + 1) * 2)
+    let mut depth = 0;
+    loop {
+        while i < chars.len() && chars[i].is_whitespace() { i += 1 }
+        if i >= chars.len() { return None }
+        if chars[i] == ')' && depth == 0 {
+            *pos = i + 1;
+            return Some(Node::Var(name, args));
+        }
+        match parse_arg(chars, &mut i, &mut depth) {
+            Some(arg) => args.push(arg),
+            None => return None
+        }
+    }
+}
+
+fn parse_arg(chars: &[char], pos: &mut usize, depth: &mut usize) -> Option<Arg> {
+    let quoted = chars[*pos] == '"';
+    if quoted { *pos += 1 }
+    let mut nodes = Vec::new();
+    let mut text = String::new();
+    loop {
+        if *pos >= chars.len() {
+            // an unterminated quote or variable
+            return None;
+        }
+        match chars[*pos] {
+            '\\' if *pos + 1 < chars.len() && is_escapable(chars[*pos+1]) => {
+                text.push(chars[*pos+1]);
+                *pos += 2;
+            }
+            '"' if quoted => {
+                *pos += 1;
+                break;
+            }
+            ')' if !quoted && *depth > 0 => {
+                text.push(')');
+                *depth -= 1;
+                *pos += 1;
+            }
+            ')' if !quoted => break,
+            c if c.is_whitespace() && !quoted => break,
+            '(' => {
+                match parse_var(chars, pos) {
+                    Some(node) => {
+                        if !text.is_empty() { nodes.push(Node::Text(mem::replace(&mut text, String::new()))) }
+                        nodes.push(node);
+                    }
+                    None => {
+                        text.push('(');
+                        if !quoted { *depth += 1 }
+                        *pos += 1;
+                    }
+                }
+            }
+            c => {
+                text.push(c);
+                *pos += 1;
+            }
+        }
+    }
+    if !text.is_empty() { nodes.push(Node::Text(text)) }
+    return Some(Arg { nodes: nodes, quoted: quoted });
+}
+
+// parses and evaluates a template in a single pass. the output of a variable is never expanded again
+pub fn render(template: &str, client: Option<Arc<IrcClient>>, channel: String, irc_message: Option<Message>, cargs: Vec<String>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>), runtime: bool) -> String {
+    let nodes = parse(template);
+    let mut rt = if runtime { Some(Runtime::new().expect("runtime:new")) } else { None };
+    let message = eval(&nodes, &client, &channel, &irc_message, &cargs, &db, &mut rt);
+    if let Some(rt) = rt { rt.shutdown_now(); }
+    return message;
+}
+
+pub fn eval(nodes: &[Node], client: &Option<Arc<IrcClient>>, channel: &str, irc_message: &Option<Message>, cargs: &[String], db: &(Sender<Vec<String>>, Receiver<Result<Value, String>>), rt: &mut Option<Runtime>) -> String {
+    let mut message = String::new();
+    for node in nodes {
+        match node {
+            Node::Text(text) => message.push_str(text),
+            Node::Var(name, args) if name == "if" => message.push_str(&eval_if(args, client, channel, irc_message, cargs, db, rt)),
+            Node::Var(name, _args) if name == "else" => {}
+            Node::Var(name, args) => {
+                // unquoted arguments are split on whitespace after they're evaluated, quoted ones are kept whole
+                let mut vargs: Vec<String> = Vec::new();
+                for arg in args {
+                    let value = eval(&arg.nodes, client, channel, irc_message, cargs, db, rt);
+                    if arg.quoted {
+                        vargs.push(value);
+                    } else {
+                        vargs.extend(value.split_whitespace().map(|str| str.to_owned()));
+                    }
+                }
+                message.push_str(&call_var(name, vargs, client, channel, irc_message, cargs, db, rt));
+            }
+        }
+    }
+    return message;
+}
+
+// (if [value] [operator] [value] ... (else) ...) or (if [value] ... (else) ...), where a lone value is true when it isn't empty.
+// only the branch that's taken is evaluated, so variables with side effects like (counterinc) are safe to use
+fn eval_if(args: &[Arg], client: &Option<Arc<IrcClient>>, channel: &str, irc_message: &Option<Message>, cargs: &[String], db: &(Sender<Vec<String>>, Receiver<Result<Value, String>>), rt: &mut Option<Runtime>) -> String {
+    let split = args.iter().position(|arg| {
+        match arg.nodes.as_slice() {
+            [Node::Var(name, vargs)] => !arg.quoted && name == "else" && vargs.is_empty(),
+            _ => false
+        }
+    });
+    let (then, otherwise) = match split {
+        Some(i) => (&args[..i], &args[i+1..]),
+        None => (args, &args[args.len()..])
+    };
+    if then.is_empty() { return "".to_owned() }
+
+    let (result, body) = match then.get(1).and_then(|arg| operator(arg)) {
+        Some(op) if then.len() >= 3 => {
+            let lhs = eval(&then[0].nodes, client, channel, irc_message, cargs, db, rt);
+            let rhs = eval(&then[2].nodes, client, channel, irc_message, cargs, db, rt);
+            (compare(lhs.trim(), op, rhs.trim()), &then[3..])
+        }
+        _ => {
+            let value = eval(&then[0].nodes, client, channel, irc_message, cargs, db, rt);
+            (!value.trim().is_empty(), &then[1..])
+        }
+    };
+
+    let branch = if result { body } else { otherwise };
+    let words: Vec<String> = branch.iter().map(|arg| eval(&arg.nodes, client, channel, irc_message, cargs, db, rt)).filter(|word| !word.is_empty()).collect();
+    return words.join(" ");
+}
+
+fn operator(arg: &Arg) -> Option<&str> {
+    match arg.nodes.as_slice() {
+        [Node::Text(text)] if !arg.quoted && OPERATORS.contains(&text.as_ref()) => Some(text),
+        _ => None
+    }
+}
+
+// numbers are compared by value, anything else is compared as text ignoring case
+fn compare(lhs: &str, op: &str, rhs: &str) -> bool {
+    if let (Ok(a), Ok(b)) = (lhs.parse::<f64>(), rhs.parse::<f64>()) {
+        match op {
+            "==" => a == b,
+            "!=" => a != b,
+            ">" => a > b,
+            "<" => a < b,
+            ">=" => a >= b,
+            "<=" => a <= b,
+            _ => lhs.contains(rhs)
+        }
+    } else {
+        let a = lhs.to_lowercase();
+        let b = rhs.to_lowercase();
+        match op {
+            "==" => a == b,
+            "!=" => a != b,
+            ">" => a > b,
+            "<" => a < b,
+            ">=" => a >= b,
+            "<=" => a <= b,
+            _ => a.contains(&b)
+        }
+    }
+}
+
+fn call_var(name: &str, vargs: Vec<String>, client: &Option<Arc<IrcClient>>, channel: &str, irc_message: &Option<Message>, cargs: &[String], db: &(Sender<Vec<String>>, Receiver<Result<Value, String>>), rt: &mut Option<Runtime>) -> String {
+    if let Some(var) = command_vars.iter().find(|var| var.0 == name) {
+        return (var.1)(client.clone(), channel.to_owned(), irc_message.clone(), vargs, cargs.to_vec(), db.clone());
+    }
+    if let Some(var) = command_vars_async.iter().find(|var| var.0 == name) {
+        if let Some((builder, func)) = (var.1)(client.clone(), channel.to_owned(), irc_message.clone(), vargs, cargs.to_vec(), db.clone()) {
+            let db = db.clone();
+            let chan = channel.to_owned();
+            let future = builder.send().and_then(|mut res| { (Ok(chan), Ok(db), mem::replace(res.body_mut(), Decoder::empty()).concat2()) }).map_err(|e| println!("request error: {}", e)).map(func);
+            let res = match rt {
+                Some(rt) => rt.block_on(future),
+                None => future.wait()
+            };
+            return res.unwrap_or("".to_owned());
+        }
+    }
+    return "".to_owned();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> Node { Node::Text(text.to_owned()) }
+    fn var(name: &str, args: Vec<Arg>) -> Node { Node::Var(name.to_owned(), args) }
+    fn arg(nodes: Vec<Node>) -> Arg { Arg { nodes: nodes, quoted: false } }
+    fn quoted(nodes: Vec<Node>) -> Arg { Arg { nodes: nodes, quoted: true } }
+
+    #[test]
+    fn parse_vars_in_text() {
+        assert_eq!(parse("hi (user)!"), vec![text("hi "), var("user", vec![]), text("!")]);
+        assert_eq!(parse("(user))"), vec![var("user", vec![]), text(")")]);
+    }
+
+    #[test]
+    fn parse_nested_vars() {
+        assert_eq!(parse("(counter (args 1))"), vec![var("counter", vec![arg(vec![var("args", vec![arg(vec![text("1")])])])])]);
+        assert_eq!(parse("(uservar (user)_deaths)"), vec![var("uservar", vec![arg(vec![var("user", vec![]), text("_deaths")])])]);
+    }
+
+    #[test]
+    fn parse_balanced_parens_in_args() {
+        assert_eq!(parse("(math ((counter deaths) + 1) * 2) left"), vec![
+            var("math", vec![
+                arg(vec![text("("), var("counter", vec![arg(vec![text("deaths")])])]),
+                arg(vec![text("+")]),
+                arg(vec![text("1)")]),
+                arg(vec![text("*")]),
+                arg(vec![text("2")])
+            ]),
+            text(" left")
+        ]);
+    }
+
+    #[test]
+    fn parse_unbalanced_parens_as_text() {
+        assert_eq!(parse("(args 1"), vec![text("(args 1")]);
+        assert_eq!(parse("(math (1 + 2)"), vec![text("(math (1 + 2)")]);
+        assert_eq!(parse("(args \"1)"), vec![text("(args \"1)")]);
+        assert_eq!(parse("(foo) (bar 1) :)"), vec![text("(foo) (bar 1) :)")]);
+    }
+
+    #[test]
+    fn parse_escapes() {
+        assert_eq!(parse("\\(user\\)"), vec![text("(user)")]);
+        assert_eq!(parse("C:\\temp \\\\ \\\""), vec![text("C:\\temp \\ \"")]);
+        assert_eq!(parse("(args \\(1\\))"), vec![var("args", vec![arg(vec![text("(1)")])])]);
+        assert_eq!(parse("(args \"say \\\"hi\\\"\")"), vec![var("args", vec![quoted(vec![text("say \"hi\"")])])]);
+    }
+
+    #[test]
+    fn parse_quoted_args() {
+        assert_eq!(parse("(cmd \"hello (user) world\" (user))"), vec![
+            var("cmd", vec![
+                quoted(vec![text("hello "), var("user", vec![]), text(" world")]),
+                arg(vec![var("user", vec![])])
+            ])
+        ]);
+    }
+
+    #[test]
+    fn compare_numbers_and_text() {
+        assert!(compare("10", ">", "9"));
+        assert!(compare("1.0", "==", "1"));
+        assert!(compare("-1", "<=", "0"));
+        assert!(compare("Hello", "==", "hello"));
+        assert!(compare("abc", "!=", "abd"));
+        assert!(!compare("abc", ">", "abd"));
+        assert!(compare("Hello World", "contains", "WORLD"));
+        assert!(!compare("Hello World", "contains", "planet"));
+    }
+}
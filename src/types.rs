@@ -1,19 +1,16 @@
 use crate::util::*;
-use crate::commands::*;
-use std::{thread,mem};
+use crate::template::*;
+use std::thread;
 use std::collections::{HashMap,HashSet};
 use serenity::client::{Context, EventHandler};
 use serenity::model::channel::Message;
 use serde::{Serialize, Deserialize};
 use serde_json::Value;
-use regex::{Regex,Captures,escape};
+use regex::{Regex,Captures};
 use crossbeam_channel::{Sender,Receiver};
 use redis::from_redis_value;
 use rocket_contrib::database;
 use rocket_contrib::databases::redis;
-use reqwest::r#async::Decoder;
-use futures::stream::Stream;
-use futures::future::{Future,join_all};
 
 pub struct DiscordHandler {
     pub channel: String,
@@ -36,36 +33,10 @@ impl EventHandler for DiscordHandler {
                 // TODO: expand aliases
                 let res: Result<redis::Value,_> = redis_call(db.clone(), vec!["hget", &format!("channel:{}:commands:{}", self.channel, word), "message"]);
                 if let Ok(value) = res {
-                    let mut message: String = from_redis_value(&value).unwrap();
-                    for var in command_vars.iter() {
-                        message = parse_var(var, &message, None, self.channel.clone(), None, args.clone(), db.clone());
-                    }
-
-                    let mut futures = Vec::new();
-                    let mut regexes: Vec<String> = Vec::new();
-                    for var in command_vars_async.iter() {
-                        let rgx = Regex::new(&format!("\\({} ?((?:[\\w\\-\\?\\._:/&!= ]+)*)\\)", var.0)).unwrap();
-                        for captures in rgx.captures_iter(&message) {
-                            if let (Some(capture), Some(vargs)) = (captures.get(0), captures.get(1)) {
-                                let vargs: Vec<String> = vargs.as_str().split_whitespace().map(|str| str.to_owned()).collect();
-                                if let Some((builder, func)) = (var.1)(None, self.channel.clone(), None, vargs.clone(), args.clone(), db.clone()) {
-                                    let db = db.clone();
-                                    let channel = self.channel.clone();
-                                    let future = builder.send().and_then(|mut res| { (Ok(channel), Ok(db), mem::replace(res.body_mut(), Decoder::empty()).concat2()) }).map(func);
-                                    futures.push(future);
-                                    regexes.push(capture.as_str().to_owned());
-                                }
-                            }
-                        }
-                    }
-
+                    let message: String = from_redis_value(&value).unwrap();
+                    let channel = self.channel.clone();
                     thread::spawn(move || {
-                        let mut core = tokio_core::reactor::Core::new().unwrap();
-                        let work = join_all(futures);
-                        for (i,res) in core.run(work).unwrap().into_iter().enumerate() {
-                            let rgx = Regex::new(&escape(&regexes[i])).unwrap();
-                            message = rgx.replace(&message, |_: &Captures| { &res }).to_string();
-                        }
+                        let message = render(&message, None, channel, None, args, db.clone(), true);
                         let _ = msg.channel_id.say(&ctx.http, message);
                     });
                 }
@@ -104,6 +75,51 @@ pub enum ClientAction {
     Part(String)
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ModerationEntry {
+    pub timestamp: String,
+    pub nick: String,
+    pub rule: String,
+    pub message: String,
+    pub action: String,
+    pub length: String
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Role {
+    Everyone,
+    Regular,
+    Subscriber,
+    VIP,
+    Moderator,
+    Broadcaster
+}
+
+impl Role {
+    pub fn parse(role: &str) -> Option<Role> {
+        match role.to_lowercase().as_ref() {
+            "everyone" => Some(Role::Everyone),
+            "regular" | "regulars" => Some(Role::Regular),
+            "subscriber" | "subscribers" | "sub" | "subs" => Some(Role::Subscriber),
+            "vip" | "vips" => Some(Role::VIP),
+            "moderator" | "moderators" | "mod" | "mods" => Some(Role::Moderator),
+            "broadcaster" => Some(Role::Broadcaster),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Role::Everyone => "everyone",
+            Role::Regular => "regular",
+            Role::Subscriber => "subscriber",
+            Role::VIP => "vip",
+            Role::Moderator => "moderator",
+            Role::Broadcaster => "broadcaster"
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DiscordOpCode {
     pub op: u16,
@@ -455,12 +471,29 @@ pub struct ApiData {
     pub state: String,
     pub fields: HashMap<String, String>,
     pub commands: HashMap<String, String>,
+    pub cooldowns: HashMap<String, HashMap<String,String>>,
     pub notices: HashMap<String, Vec<String>>,
     pub settings: HashMap<String, String>,
     pub blacklist: HashMap<String, HashMap<String,String>>,
     pub keywords: HashMap<String, HashMap<String,String>>,
     pub songreqs: Vec<(String,String,String)>,
-    pub integrations: HashMap<String, HashMap<String,String>>
+    pub integrations: HashMap<String, HashMap<String,String>>,
+    pub permissions: HashMap<String, String>,
+    pub filters: HashMap<String, HashMap<String,String>>,
+    pub events: HashMap<String, HashMap<String,String>>,
+    pub subs: HashMap<String,String>,
+    pub greetings: HashMap<String, HashMap<String,String>>,
+    pub points: Vec<(String,i64)>,
+    pub quotes: HashMap<String, HashMap<String,String>>,
+    pub counters: HashMap<String,String>
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiPoll {
+    pub question: String,
+    pub open: bool,
+    pub ends: String,
+    pub options: Vec<(String,u32)>
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -540,6 +573,13 @@ pub struct ApiLogsReq {
     pub num: String
 }
 
+#[derive(FromForm)]
+pub struct ApiModerationLogReq {
+    pub num: String,
+    pub nick: Option<String>,
+    pub rule: Option<String>
+}
+
 #[derive(Serialize, FromForm)]
 pub struct ApiLoginReq {
     pub channel: String,
@@ -571,7 +611,10 @@ pub struct ApiGameReq {
 #[derive(FromForm)]
 pub struct ApiSaveCommandReq {
     pub command: String,
-    pub message: String
+    pub message: String,
+    pub cooldown: Option<String>,
+    pub user_cooldown: Option<String>,
+    pub cooldown_message: Option<String>
 }
 
 #[derive(FromForm)]
@@ -579,6 +622,17 @@ pub struct ApiTrashCommandReq {
     pub command: String
 }
 
+#[derive(FromForm)]
+pub struct ApiSavePermissionReq {
+    pub command: String,
+    pub role: String
+}
+
+#[derive(FromForm)]
+pub struct ApiTrashPermissionReq {
+    pub command: String
+}
+
 #[derive(FromForm)]
 pub struct ApiNoticeReq {
     pub interval: String,
@@ -614,17 +668,91 @@ pub struct ApiTrashBlacklistReq {
     pub key: String
 }
 
+#[derive(FromForm)]
+pub struct ApiSaveFilterReq {
+    pub filter: String,
+    pub limit: String,
+    pub trigger: Option<String>,
+    pub subs: Option<String>
+}
+
+#[derive(FromForm)]
+pub struct ApiTrashFilterReq {
+    pub filter: String
+}
+
+#[derive(FromForm)]
+pub struct ApiSaveEventReq {
+    pub bits: String,
+    pub etype: String,
+    pub value: String
+}
+
+#[derive(FromForm)]
+pub struct ApiTrashEventReq {
+    pub bits: String
+}
+
+#[derive(FromForm)]
+pub struct ApiSaveGreetingReq {
+    pub nick: String,
+    pub hours: String,
+    pub message: String
+}
+
+#[derive(FromForm)]
+pub struct ApiTrashGreetingReq {
+    pub nick: String
+}
+
+#[derive(FromForm)]
+pub struct ApiSaveCounterReq {
+    pub name: String,
+    pub value: String
+}
+
+#[derive(FromForm)]
+pub struct ApiTrashCounterReq {
+    pub name: String
+}
+
+#[derive(FromForm)]
+pub struct ApiNewQuoteReq {
+    pub quote: String,
+    pub game: Option<String>
+}
+
+#[derive(FromForm)]
+pub struct ApiSaveQuoteReq {
+    pub id: String,
+    pub quote: String,
+    pub game: Option<String>
+}
+
+#[derive(FromForm)]
+pub struct ApiTrashQuoteReq {
+    pub id: String
+}
+
 #[derive(FromForm)]
 pub struct ApiNewKeywordReq {
     pub regex: String,
-    pub command: String
+    pub command: String,
+    pub message: Option<String>,
+    pub cooldown: Option<String>,
+    pub sensitive: Option<String>,
+    pub exempt: Option<String>
 }
 
 #[derive(FromForm)]
 pub struct ApiSaveKeywordReq {
     pub regex: String,
     pub command: String,
-    pub key: String
+    pub key: String,
+    pub message: Option<String>,
+    pub cooldown: Option<String>,
+    pub sensitive: Option<String>,
+    pub exempt: Option<String>
 }
 
 #[derive(FromForm)]
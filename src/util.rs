@@ -1,5 +1,6 @@
 use crate::types::*;
 use crate::commands::*;
+use crate::template::*;
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::{thread,mem,time};
@@ -12,10 +13,10 @@ use crossbeam_channel::{Sender,Receiver};
 use reqwest::Method;
 use reqwest::r#async::{RequestBuilder,Decoder};
 use futures::future::Future;
-use tokio::runtime::Runtime;
 use irc::client::prelude::*;
-use regex::{Regex,RegexBuilder,Captures,escape};
+use regex::{Regex,RegexBuilder,Captures};
 use redis::{self,Value,Commands,from_redis_value};
+use url::Url;
 
 pub fn log_info(id: Option<Either<&str, Vec<&str>>>, descriptor: &str, content: &str, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) {
     let timestamp = Utc::now().format("%Y-%m-%dT%H:%M:%S%z");
@@ -168,13 +169,30 @@ pub fn redis_call(db: (Sender<Vec<String>>, Receiver<Result<Value, String>>), ar
     }
 }
 
+// moderation commands are sent ahead of chatter
+const PRIORITY_COMMANDS: [&str; 4] = ["/timeout", "/ban", "/delete", "/unban"];
+const QUEUE_LIMIT: usize = 100;
+
+pub fn queue_privmsg(client: &IrcClient, channel: &str, message: &str, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) {
+    let entry = format!("{} {}", channel, message);
+    let key = format!("bot:{}:queue", client.current_nickname());
+    if PRIORITY_COMMANDS.iter().any(|cmd| message.starts_with(cmd)) {
+        // the queue is popped from the right
+        redis_call(db.clone(), vec!["rpush", &key, &entry]);
+    } else {
+        redis_call(db.clone(), vec!["lpush", &key, &entry]);
+        // once the queue is full new messages are dropped rather than sent long after they were meant for
+        redis_call(db.clone(), vec!["ltrim", &key, &format!("-{}", QUEUE_LIMIT), "-1"]);
+    }
+}
+
 pub fn send_message(client: Arc<IrcClient>, channel: String, mut message: String, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) {
     thread::spawn(move || {
         let auth: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:auth", channel)]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
         if auth == "true" {
             let me: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:settings", channel), "channel:me"]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
             if me == "true" { message = format!("/me {}", message); }
-            let _ = client.send_privmsg(format!("#{}", channel), message);
+            queue_privmsg(&client, &channel, &message, db.clone());
         }
     });
 }
@@ -187,51 +205,143 @@ pub fn send_parsed_message(client: Arc<IrcClient>, channel: String, mut message:
                 if char == '@' { message = format!("{} -> {}", args[args.len()-1], message) }
             }
         }
+        message = render(&message, Some(client.clone()), channel.clone(), irc_message, args, db.clone(), runtime);
+
+        // keep /me on every part of a split message
         let me: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:settings", channel), "channel:me"]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
-        if me == "true" { message = format!("/me {}", message); }
+        let mut me = me == "true";
+        if message.starts_with("/me ") {
+            me = true;
+            message = message[4..].to_owned();
+        }
+        let max: String = from_redis_value(&redis_call(db.clone(), vec!["hget", &format!("channel:{}:settings", channel), "channel:max-parts"]).unwrap_or(Value::Data("3".as_bytes().to_owned()))).unwrap();
+        let max: usize = max.parse().unwrap_or(3);
+        for part in split_message(&message, 480, max) {
+            if me {
+                queue_privmsg(&client, &channel, &format!("/me {}", part), db.clone());
+            } else {
+                queue_privmsg(&client, &channel, &part, db.clone());
+            }
+        }
+    }
+}
 
-        for var in command_vars.iter() {
-            message = parse_var(var, &message, Some(client.clone()), channel.clone(), irc_message.clone(), args.clone(), db.clone());
+// splits a message into parts that fit within twitch's 500 character limit, breaking on (split) markers
+// and word boundaries. parts split for length are numbered against the parts that are actually sent, and
+// a part followed by dropped text ends with "..."
+pub fn split_message(message: &str, limit: usize, max_parts: usize) -> Vec<String> {
+    let mut segments: Vec<Vec<String>> = Vec::new();
+    for segment in message.split("(split)") {
+        let segment = segment.trim();
+        if segment.is_empty() { continue }
+        if segment.chars().count() <= limit {
+            segments.push(vec![segment.to_owned()]);
+            continue;
         }
 
-        let mut futures = Vec::new();
-        let mut regexes: Vec<String> = Vec::new();
-        for var in command_vars_async.iter() {
-            let rgx = Regex::new(&format!("\\({} ?((?:[\\w\\-\\?\\._:/&!= ]+)*)\\)", var.0)).unwrap();
-            for captures in rgx.captures_iter(&message) {
-                if let (Some(capture), Some(vargs)) = (captures.get(0), captures.get(1)) {
-                    let vargs: Vec<String> = vargs.as_str().split_whitespace().map(|str| str.to_owned()).collect();
-                    if let Some((builder, func)) = (var.1)(Some(client.clone()), channel.clone(), irc_message.clone(), vargs.clone(), args.clone(), db.clone()) {
-                        let db = db.clone();
-                        let chan = channel.clone();
-                        let future = builder.send().and_then(|mut res| { (Ok(chan), Ok(db), mem::replace(res.body_mut(), Decoder::empty()).concat2()) }).map_err(|e| println!("request error: {}", e)).map(func);
-                        futures.push(future);
-                        regexes.push(capture.as_str().to_owned());
-                    }
-                }
+        let mut chunks: Vec<String> = Vec::new();
+        let mut chunk = String::new();
+        for word in segment.split_whitespace() {
+            let mut word = word.to_owned();
+            while word.chars().count() > limit {
+                if !chunk.is_empty() { chunks.push(mem::replace(&mut chunk, String::new())) }
+                chunks.push(word.chars().take(limit).collect());
+                word = word.chars().skip(limit).collect();
+            }
+            if word.is_empty() { continue }
+            if chunk.is_empty() {
+                chunk = word;
+            } else if chunk.chars().count() + word.chars().count() + 1 > limit {
+                chunks.push(mem::replace(&mut chunk, word));
+            } else {
+                chunk = format!("{} {}", chunk, word);
             }
         }
+        if !chunk.is_empty() { chunks.push(chunk) }
+        segments.push(chunks);
+    }
 
-        if runtime {
-            let mut rt = Runtime::new().expect("runtime:new");
-            for (i,future) in futures.into_iter().enumerate() {
-                let res = rt.block_on(future).unwrap();
-                let rgx = Regex::new(&escape(&regexes[i])).expect("regex:new");
-                message = rgx.replace(&message, |_: &Captures| { &res }).to_string();
+    let mut parts: Vec<String> = Vec::new();
+    let total: usize = segments.iter().map(|chunks| chunks.len()).sum();
+    for chunks in segments {
+        if parts.len() >= max_parts { break }
+        let numbered = chunks.len() > 1;
+        let sent = chunks.len().min(max_parts - parts.len());
+        for (i, chunk) in chunks.into_iter().take(sent).enumerate() {
+            if numbered {
+                parts.push(format!("({}/{}) {}", i+1, sent, chunk));
+            } else {
+                parts.push(chunk);
             }
-            rt.shutdown_now();
-        } else {
-            for (i,future) in futures.into_iter().enumerate() {
-                let res = future.wait().unwrap();
-                let rgx = Regex::new(&escape(&regexes[i])).expect("regex:new");
-                message = rgx.replace(&message, |_: &Captures| { &res }).to_string();
+        }
+    }
+    if total > parts.len() {
+        if let Some(last) = parts.last_mut() { last.push_str(" ...") }
+    }
+    return parts;
+}
+
+pub fn log_moderation(channel: &str, nick: &str, rule: &str, content: &str, action: &str, length: &str, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) {
+    let entry = ModerationEntry { timestamp: Utc::now().to_rfc3339(), nick: nick.to_owned(), rule: rule.to_owned(), message: content.to_owned(), action: action.to_owned(), length: length.to_owned() };
+    if let Ok(json) = serde_json::to_string(&entry) {
+        redis_call(db.clone(), vec!["lpush", &format!("channel:{}:moderation:log", channel), &json]);
+        redis_call(db.clone(), vec!["ltrim", &format!("channel:{}:moderation:log", channel), "0", "9999"]);
+    }
+}
+
+// records a strike against a user and applies the matching step of the channel's strike ladder,
+// falling back to a plain timeout of the given length when no ladder has been set
+pub fn strike(client: Arc<IrcClient>, channel: &str, nick: &str, message_id: Option<String>, content: &str, rule: &str, length: &str, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) {
+    let display: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:display", channel)]).unwrap_or(Value::Data("false".as_bytes().to_owned()))).unwrap();
+    let reason = match rule {
+        "colors" => "posting colors",
+        "age" => "not reaching the minimum account age",
+        "caps" => "posting too many caps",
+        "symbols" => "posting too many symbols",
+        "length" => "posting a message that is too long",
+        "emotes" => "posting too many emotes",
+        "repeats" => "repeating messages",
+        "copypasta" => "posting copypasta",
+        "links" => "posting links",
+        "blacklist" => "posting a blacklisted phrase",
+        _ => "breaking the rules"
+    };
+    let ladder: Vec<String> = from_redis_value(&redis_call(db.clone(), vec!["lrange", &format!("channel:{}:moderation:strikes", channel), "0", "-1"]).unwrap_or(Value::Bulk(Vec::new()))).unwrap();
+    if ladder.len() == 0 {
+        queue_privmsg(&client, channel, &format!("/timeout {} {}", nick, length), db.clone());
+        log_moderation(channel, nick, rule, content, "timeout", length, db.clone());
+        if display == "true" { send_message(client.clone(), channel.to_owned(), format!("@{} you've been timed out for {}", nick, reason), db.clone()); }
+    } else {
+        let decay: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:moderation:strikes:decay", channel)]).unwrap_or(Value::Data("3600".as_bytes().to_owned()))).unwrap();
+        let count: i64 = from_redis_value(&redis_call(db.clone(), vec!["incr", &format!("channel:{}:strikes:{}", channel, nick)]).unwrap_or(Value::Int(1))).unwrap_or(1);
+        redis_call(db.clone(), vec!["expire", &format!("channel:{}:strikes:{}", channel, nick), &decay]);
+        let mut step = count as usize;
+        if step > ladder.len() { step = ladder.len() }
+        if step < 1 { step = 1 }
+        match ladder[step-1].as_ref() {
+            "delete" => {
+                match message_id {
+                    Some(id) => { queue_privmsg(&client, channel, &format!("/delete {}", id), db.clone()); }
+                    None => { queue_privmsg(&client, channel, &format!("/timeout {} 1", nick), db.clone()); }
+                }
+                log_moderation(channel, nick, rule, content, "delete", "", db.clone());
+                if display == "true" { send_message(client.clone(), channel.to_owned(), format!("@{} your message has been deleted for {} (strike {})", nick, reason, count), db.clone()); }
+            }
+            "ban" => {
+                queue_privmsg(&client, channel, &format!("/ban {}", nick), db.clone());
+                log_moderation(channel, nick, rule, content, "ban", "", db.clone());
+                if display == "true" { send_message(client.clone(), channel.to_owned(), format!("@{} you've been banned for {} (strike {})", nick, reason, count), db.clone()); }
+            }
+            length => {
+                queue_privmsg(&client, channel, &format!("/timeout {} {}", nick, length), db.clone());
+                log_moderation(channel, nick, rule, content, "timeout", length, db.clone());
+                if display == "true" { send_message(client.clone(), channel.to_owned(), format!("@{} you've been timed out for {} (strike {})", nick, reason, count), db.clone()); }
             }
         }
-        let _ = client.send_privmsg(format!("#{}", channel), message);
     }
 }
 
-pub fn spawn_age_check(client: Arc<IrcClient>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>), channel: String, nick: String, age: i64, display: String) {
+pub fn spawn_age_check(client: Arc<IrcClient>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>), channel: String, nick: String, message_id: Option<String>, content: String, age: i64) {
     let res: Result<Value,_> = redis_call(db.clone(), vec!["hget", "account:ages", &nick]);
     if let Ok(value) = res {
         let timestamp: String = from_redis_value(&value).unwrap();
@@ -239,8 +349,7 @@ pub fn spawn_age_check(client: Arc<IrcClient>, db: (Sender<Vec<String>>, Receive
         let diff = Utc::now().signed_duration_since(dt);
         if diff.num_minutes() < age {
             let length = age - diff.num_minutes();
-            let _ = client.send_privmsg(format!("#{}", channel), format!("/timeout {} {}", nick, length * 60));
-            if display == "true" { send_message(client.clone(), channel.to_owned(), format!("@{} you've been timed out for not reaching the minimum account age", nick), db.clone()); }
+            strike(client.clone(), &channel, &nick, message_id, &content, "age", &(length * 60).to_string(), db.clone());
         }
     } else {
         let token: String = from_redis_value(&redis_call(db.clone(), vec!["get", &format!("channel:{}:token", &channel)]).expect(&format!("channel:{}:token", &channel))).unwrap();
@@ -262,8 +371,7 @@ pub fn spawn_age_check(client: Arc<IrcClient>, db: (Sender<Vec<String>>, Receive
                             let diff = Utc::now().signed_duration_since(dt);
                             if diff.num_minutes() < age {
                                 let length = age - diff.num_minutes();
-                                let _ = client.send_privmsg(format!("#{}", channel), format!("/timeout {} {}", nick, length * 60));
-                                if display == "true" { send_message(client.clone(), channel.to_owned(), format!("@{} you've been timed out for not reaching the minimum account age", nick), db.clone()); }
+                                strike(client.clone(), &channel, &nick, message_id, &content, "age", &(length * 60).to_string(), db.clone());
                             }
                         }
                     }
@@ -425,20 +533,6 @@ pub fn pubg_request(token: String, url: &str) -> RequestBuilder {
     return builder;
 }
 
-pub fn parse_var(var: &(&str, fn(Option<Arc<IrcClient>>, String, Option<Message>, Vec<String>, Vec<String>, (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> String), message: &str, client: Option<Arc<IrcClient>>, channel: String, irc_message: Option<Message>, cargs: Vec<String>, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> String {
-    let rgx = Regex::new(&format!("\\({} ?((?:[\\w\\-\\?\\._:/&!= ]+)*)\\)", var.0)).unwrap();
-    let mut msg: String = message.to_owned();
-    for captures in rgx.captures_iter(message) {
-        if let Some(capture) = captures.get(1) {
-            let vargs: Vec<String> = capture.as_str().split_whitespace().map(|str| str.to_owned()).collect();
-            let res = (var.1)(client.clone(), channel.clone(), irc_message.clone(), vargs, cargs.clone(), db.clone());
-            msg = rgx.replace(&msg, |_: &Captures| { &res }).to_string();
-        }
-    }
-
-    return msg.to_owned();
-}
-
 /*pub fn parse_code(message: &str) -> String {
     let mut msg: String = message.to_owned();
     let rgx = Regex::new("\\{-(.+?)\\-}").unwrap();
@@ -490,6 +584,20 @@ pub fn get_id(msg: &Message) -> Option<String> {
     return id;
 }
 
+pub fn get_message_id(msg: &Message) -> Option<String> {
+    let mut id: Option<String> = None;
+    if let Some(tags) = &msg.tags {
+        tags.iter().for_each(|tag| {
+            if let Some(_value) = &tag.1 {
+                if tag.0 == "id" {
+                    id = (tag.1).clone();
+                }
+            }
+        });
+    }
+    return id;
+}
+
 pub fn get_bits(msg: &Message) -> Option<String> {
     let mut bits: Option<String> = None;
     if let Some(tags) = &msg.tags {
@@ -504,6 +612,49 @@ pub fn get_bits(msg: &Message) -> Option<String> {
     return bits;
 }
 
+pub fn get_tag(msg: &Message, name: &str) -> Option<String> {
+    let mut value: Option<String> = None;
+    if let Some(tags) = &msg.tags {
+        tags.iter().for_each(|tag| {
+            if let Some(_value) = &tag.1 {
+                if tag.0 == name {
+                    value = (tag.1).clone();
+                }
+            }
+        });
+    }
+    return value;
+}
+
+// parses the emotes tag into (emote id, start, end) ranges, e.g. 25:0-4,12-16/1902:6-10
+pub fn get_emotes(msg: &Message) -> Vec<(String, usize, usize)> {
+    let mut emotes = Vec::new();
+    if let Some(tags) = &msg.tags {
+        tags.iter().for_each(|tag| {
+            if let Some(value) = &tag.1 {
+                if tag.0 == "emotes" {
+                    for estr in value.split("/") {
+                        let emote: Vec<&str> = estr.split(":").collect();
+                        if emote.len() > 1 {
+                            for range in emote[1].split(",") {
+                                let range: Vec<&str> = range.split("-").collect();
+                                if range.len() > 1 {
+                                    let start: Result<usize,_> = range[0].parse();
+                                    let end: Result<usize,_> = range[1].parse();
+                                    if let (Ok(start), Ok(end)) = (start, end) {
+                                        emotes.push((emote[0].to_owned(), start, end));
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        });
+    }
+    return emotes;
+}
+
 pub fn get_badges(msg: &Message) -> HashMap<String, String> {
     let mut badges = HashMap::new();
     if let Some(tags) = &msg.tags {
@@ -526,10 +677,360 @@ pub fn get_badges(msg: &Message) -> HashMap<String, String> {
     return badges;
 }
 
+pub fn get_role(badges: &HashMap<String, String>, channel: &str, nick: &str, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> Role {
+    if let Some(_value) = badges.get("broadcaster") { return Role::Broadcaster }
+    if let Some(_value) = badges.get("moderator") { return Role::Moderator }
+    if let Some(_value) = badges.get("vip") { return Role::VIP }
+    if let Some(_value) = badges.get("subscriber") { return Role::Subscriber }
+    if let Some(_value) = badges.get("founder") { return Role::Subscriber }
+    let regular: bool = from_redis_value(&redis_call(db.clone(), vec!["sismember", &format!("channel:{}:regulars", channel), nick]).unwrap_or(Value::Int(0))).unwrap_or(false);
+    if regular { return Role::Regular }
+    return Role::Everyone;
+}
+
+// looks up the role required to run a command, checking the subcommand before the command itself
+pub fn get_required_role(channel: &str, command: &str, args: &Vec<String>, default: Role, db: (Sender<Vec<String>>, Receiver<Result<Value, String>>)) -> Role {
+    if args.len() > 0 {
+        let res: Result<Value,_> = redis_call(db.clone(), vec!["hget", &format!("channel:{}:permissions", channel), &format!("{} {}", command, args[0].to_lowercase())]);
+        if let Ok(value) = res {
+            let role: String = from_redis_value(&value).unwrap();
+            if let Some(role) = Role::parse(&role) { return role }
+        }
+    }
+    let res: Result<Value,_> = redis_call(db.clone(), vec!["hget", &format!("channel:{}:permissions", channel), command]);
+    if let Ok(value) = res {
+        let role: String = from_redis_value(&value).unwrap();
+        if let Some(role) = Role::parse(&role) { return role }
+    }
+    return default;
+}
+
+// finds a youtube video id in a message, either from a youtube.com/youtu.be link or a bare 11 character id.
+// cheermotes like Cheer100 are skipped since they're part of every bits message
+pub fn youtube_id(msg: &str) -> Option<String> {
+    let url = Regex::new(r"(?:youtube\.com/.*[?&]v=|youtu\.be/|youtube\.com/(?:embed|shorts)/)([\-_a-zA-Z0-9]{11})").unwrap();
+    if let Some(captures) = url.captures(msg) {
+        return Some(captures[1].to_owned());
+    }
+    let bare = Regex::new(r"^[\-_a-zA-Z0-9]{11}$").unwrap();
+    let cheer = Regex::new(r"^[a-zA-Z]+\d+$").unwrap();
+    msg.split_whitespace().find(|word| bare.is_match(word) && !cheer.is_match(word)).map(|word| word.to_owned())
+}
+
+// evaluates an arithmetic expression with + - * / % ^ and parentheses. durations such as the output
+// of (uptime), e.g. 2h15m, are read as a number of hours
+pub fn eval_math(expr: &str) -> Option<f64> {
+    let tokens = tokenize_math(expr)?;
+    let mut pos = 0;
+    let value = math_expr(&tokens, &mut pos)?;
+    if pos == tokens.len() && value.is_finite() { Some(value) } else { None }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum MathToken {
+    Num(f64),
+    Op(char),
+    Open,
+    Close
+}
+
+fn tokenize_math(expr: &str) -> Option<Vec<MathToken>> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '(' {
+            tokens.push(MathToken::Open);
+            i += 1;
+        } else if c == ')' {
+            tokens.push(MathToken::Close);
+            i += 1;
+        } else if "+-*/%^".contains(c) {
+            tokens.push(MathToken::Op(c));
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut total = 0.0;
+            let mut duration = false;
+            loop {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') { i += 1 }
+                if start == i { break }
+                let num: f64 = chars[start..i].iter().collect::<String>().parse().ok()?;
+                let ustart = i;
+                while i < chars.len() && chars[i].is_ascii_alphabetic() { i += 1 }
+                let unit: String = chars[ustart..i].iter().collect();
+                match unit.as_ref() {
+                    "" => { if duration { return None } total = num; break }
+                    "d" | "day" | "days" => total += num * 24.0,
+                    "h" => total += num,
+                    "m" => total += num / 60.0,
+                    "s" => total += num / 3600.0,
+                    _ => return None
+                }
+                duration = true;
+            }
+            tokens.push(MathToken::Num(total));
+        } else {
+            return None;
+        }
+    }
+    return Some(tokens);
+}
+
+fn math_expr(tokens: &[MathToken], pos: &mut usize) -> Option<f64> {
+    let mut value = math_term(tokens, pos)?;
+    while let Some(&MathToken::Op(op)) = tokens.get(*pos) {
+        if op != '+' && op != '-' { break }
+        *pos += 1;
+        let rhs = math_term(tokens, pos)?;
+        if op == '+' { value += rhs } else { value -= rhs }
+    }
+    return Some(value);
+}
+
+fn math_term(tokens: &[MathToken], pos: &mut usize) -> Option<f64> {
+    let mut value = math_factor(tokens, pos)?;
+    while let Some(&MathToken::Op(op)) = tokens.get(*pos) {
+        if op != '*' && op != '/' && op != '%' { break }
+        *pos += 1;
+        let rhs = math_factor(tokens, pos)?;
+        match op {
+            '*' => value *= rhs,
+            _ if rhs == 0.0 => return None,
+            '/' => value /= rhs,
+            _ => value %= rhs
+        }
+    }
+    return Some(value);
+}
+
+fn math_factor(tokens: &[MathToken], pos: &mut usize) -> Option<f64> {
+    let base = math_unary(tokens, pos)?;
+    if let Some(MathToken::Op('^')) = tokens.get(*pos) {
+        *pos += 1;
+        let exp = math_factor(tokens, pos)?;
+        return Some(base.powf(exp));
+    }
+    return Some(base);
+}
+
+fn math_unary(tokens: &[MathToken], pos: &mut usize) -> Option<f64> {
+    match tokens.get(*pos)? {
+        MathToken::Op('-') => { *pos += 1; math_unary(tokens, pos).map(|value| -value) }
+        MathToken::Num(num) => { *pos += 1; Some(*num) }
+        MathToken::Open => {
+            *pos += 1;
+            let value = math_expr(tokens, pos)?;
+            if tokens.get(*pos) != Some(&MathToken::Close) { return None }
+            *pos += 1;
+            Some(value)
+        }
+        _ => None
+    }
+}
+
+// dice coefficient of the character bigrams of both strings, from 0.0 to 1.0
+pub fn similarity(a: &str, b: &str) -> f32 {
+    if a == b { return 1.0 }
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len() < 2 || b.len() < 2 { return 0.0 }
+    let mut bigrams: HashMap<(char, char), usize> = HashMap::new();
+    for pair in a.windows(2) { *bigrams.entry((pair[0], pair[1])).or_insert(0) += 1 }
+    let mut matches = 0;
+    for pair in b.windows(2) {
+        if let Some(num) = bigrams.get_mut(&(pair[0], pair[1])) {
+            if *num > 0 {
+                *num -= 1;
+                matches += 1;
+            }
+        }
+    }
+    return (2 * matches) as f32 / (a.len() + b.len() - 2) as f32;
+}
+
 fn strip_chars(original : &str, strip : &str) -> String {
     original.chars().filter(|&c| !strip.contains(c)).collect()
 }
 
+// folds obfuscated links back into plain ones before they're matched against url_regex,
+// e.g. example(dot)com, example . com, zero-width spaces and lookalike characters
+pub fn normalize_links(msg: &str) -> String {
+    let folded: String = msg.chars().filter_map(|c| {
+        match c {
+            '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' | '\u{FEFF}' | '\u{00AD}' => None,
+            '\u{FF01}'..='\u{FF5E}' => std::char::from_u32(c as u32 - 0xFEE0),
+            '\u{3002}' | '\u{FF61}' | '\u{2024}' | '\u{FE52}' => Some('.'),
+            'а' | 'А' | 'α' | 'Α' => Some('a'),
+            'в' | 'В' | 'β' | 'Β' => Some('b'),
+            'с' | 'С' | 'ϲ' => Some('c'),
+            'ԁ' => Some('d'),
+            'е' | 'Е' | 'ε' | 'Ε' => Some('e'),
+            'һ' | 'Н' | 'Η' => Some('h'),
+            'і' | 'І' | 'ι' | 'Ι' => Some('i'),
+            'ј' | 'Ј' => Some('j'),
+            'к' | 'К' | 'κ' | 'Κ' => Some('k'),
+            'М' | 'Μ' => Some('m'),
+            'п' | 'η' | 'Ν' => Some('n'),
+            'о' | 'О' | 'ο' | 'Ο' => Some('o'),
+            'р' | 'Р' | 'ρ' | 'Ρ' => Some('p'),
+            'ѕ' | 'Ѕ' => Some('s'),
+            'т' | 'Т' | 'τ' | 'Τ' => Some('t'),
+            'υ' | 'ν' => Some('v'),
+            'ԝ' | 'ѡ' => Some('w'),
+            'х' | 'Х' | 'χ' | 'Χ' => Some('x'),
+            'у' | 'У' | 'γ' | 'Υ' => Some('y'),
+            'Ζ' => Some('z'),
+            _ => Some(c)
+        }
+    }).collect();
+    let bracketed = Regex::new(r"(?i)\s*[\(\[\{<]\s*(dot|\.)\s*[\)\]\}>]\s*").unwrap();
+    let spelled = Regex::new(r"(?i)\s+dot\s+(com|net|org|tv|gg|io|co|ly|be|ru|xyz)\b").unwrap();
+    let spaced = Regex::new(r"([a-zA-Z0-9\-])\s+\.\s*([a-zA-Z0-9])").unwrap();
+    let folded = bracketed.replace_all(&folded, ".").to_string();
+    let folded = spelled.replace_all(&folded, ".$1").to_string();
+    return spaced.replace_all(&folded, "$1.$2").to_string();
+}
+
+// matches a url against a whitelist or blocklist entry, either domain/path or *.domain for any subdomain
+pub fn link_matches(link: &str, url: &Url) -> bool {
+    let link: Vec<&str> = link.split("/").collect();
+    let host = link[0].to_lowercase();
+    let mut domain = url.domain().unwrap_or("");
+    if domain.starts_with("www.") { domain = &domain[4..] }
+    let matched;
+    if host.starts_with("*.") {
+        matched = domain == &host[2..] || domain.ends_with(&host[1..]);
+    } else {
+        matched = domain == host;
+    }
+    if matched && link.len() > 1 {
+        return url.path().len() > 1 && url.path()[1..] == link[1..].join("/");
+    }
+    return matched;
+}
+
 pub fn url_regex() -> Regex {
     RegexBuilder::new("(((ht|f)tp(s?))://)?(([a-zA-Z0-9\\-]+)\\.)+(aero|arpa|biz|cat|com|coop|edu|gov|info|jobs|mil|mobi|museum|name|net|org|pro|travel|ac|ad|ae|af|ag|ai|al|am|an|ao|ap|aq|ar|as|at|au|aw|az|ax|ba|bb|bd|be|bf|bg|bh|bi|bj|bm|bn|bo|br|bs|bt|bv|bw|by|bz|ca|cc|cd|cf|cg|ch|ci|ck|cl|cm|cn|co|cr|cs|cu|cv|cx|cy|cz|de|dj|dk|dm|do|dz|ec|ee|eg|eh|er|es|et|eu|fi|fj|fk|fm|fo|fr|ga|gb|gd|ge|gf|gg|gh|gi|gl|gm|gn|gp|gq|gr|gs|gt|gu|gw|gy|hk|hm|hn|hr|ht|hu|id|ie|il|im|in|io|iq|ir|is|it|je|jm|jo|jp|ke|kg|kh|ki|km|kn|kp|kr|kw|ky|kz|la|lb|lc|li|lk|lr|ls|lt|lu|lv|ly|ma|mc|md|mg|mh|mk|ml|mm|mn|mo|mp|mq|mr|ms|mt|mu|mv|mw|mx|my|mz|na|nc|ne|nf|ng|ni|nl|no|np|nr|nu|nz|om|pa|pe|pf|pg|ph|pk|pl|pm|pn|pr|ps|pt|pw|py|qa|re|ro|ru|rw|sa|sb|sc|sd|se|sg|sh|si|sj|sk|sl|sm|sn|so|sr|st|sv|sy|sz|tc|td|tf|tg|th|tj|tk|tl|tm|tn|to|tp|tr|tt|tv|tw|tz|ua|ug|uk|um|us|uy|uz|va|vc|ve|vg|vi|vn|vu|wf|ws|ye|yt|yu|za|zm|zw)(:[0-9]+)*(/($|[a-zA-Z0-9\\.,;\\?'\\\\\\+&%\\$#=~_\\-]+))*").case_insensitive(true).build().unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn similarity_scores() {
+        assert_eq!(similarity("copypasta", "copypasta"), 1.0);
+        assert_eq!(similarity("a", "b"), 0.0);
+        assert_eq!(similarity("night", "nacht"), 0.25);
+        assert!(similarity("buy followers at example", "buy followers at exampel") > 0.9);
+        assert!(similarity("hello there", "goodbye now") < 0.3);
+    }
+
+    #[test]
+    fn normalize_obfuscated_links() {
+        assert_eq!(normalize_links("example (dot) com"), "example.com");
+        assert_eq!(normalize_links("example[.]com"), "example.com");
+        assert_eq!(normalize_links("example DOT com"), "example.com");
+        assert_eq!(normalize_links("example . com"), "example.com");
+        assert_eq!(normalize_links("ｅｘａｍｐｌｅ．ｃｏｍ"), "example.com");
+        assert_eq!(normalize_links("\u{435}\u{445}\u{430}m\u{440}le.com"), "example.com");
+        assert_eq!(normalize_links("exam\u{200B}ple.c\u{200D}om"), "example.com");
+        assert_eq!(normalize_links("I dot my i's. Done"), "I dot my i's. Done");
+        assert!(url_regex().is_match(&normalize_links("go to example (dot) com now")));
+    }
+
+    #[test]
+    fn link_matching() {
+        let url = |link: &str| Url::parse(link).unwrap();
+        assert!(link_matches("example.com", &url("https://www.example.com/foo")));
+        assert!(link_matches("Example.com", &url("https://example.com")));
+        assert!(!link_matches("example.com", &url("https://sub.example.com")));
+        assert!(link_matches("*.example.com", &url("https://sub.example.com")));
+        assert!(link_matches("*.example.com", &url("https://example.com")));
+        assert!(!link_matches("*.example.com", &url("https://badexample.com")));
+        assert!(link_matches("youtube.com/watch", &url("https://www.youtube.com/watch?v=dQw4w9WgXcQ")));
+        assert!(!link_matches("youtube.com/watch", &url("https://youtube.com/channel/abc")));
+        assert!(!link_matches("youtube.com/watch", &url("https://youtube.com")));
+    }
+
+    #[test]
+    fn split_short_message() {
+        assert_eq!(split_message("hello world", 500, 3), vec!["hello world"]);
+        assert_eq!(split_message("  ", 500, 3), Vec::<String>::new());
+    }
+
+    #[test]
+    fn split_on_markers() {
+        assert_eq!(split_message("one (split) two(split)  (split)three", 500, 3), vec!["one", "two", "three"]);
+    }
+
+    #[test]
+    fn split_on_words() {
+        assert_eq!(split_message("aaa bbb ccc", 7, 3), vec!["(1/2) aaa bbb", "(2/2) ccc"]);
+    }
+
+    #[test]
+    fn split_multibyte() {
+        assert_eq!(split_message("ééé ééé", 3, 3), vec!["(1/2) ééé", "(2/2) ééé"]);
+        assert_eq!(split_message("日本語 日本", 6, 3), vec!["日本語 日本"]);
+        assert_eq!(split_message(&"😀".repeat(5), 2, 5), vec!["(1/3) 😀😀", "(2/3) 😀😀", "(3/3) 😀"]);
+        assert_eq!(split_message("a😀😀😀b", 2, 5), vec!["(1/3) a😀", "(2/3) 😀😀", "(3/3) b"]);
+    }
+
+    #[test]
+    fn split_part_cap() {
+        assert_eq!(split_message("a b c d", 1, 2), vec!["(1/2) a", "(2/2) b ..."]);
+        assert_eq!(split_message("one (split) two (split) three", 500, 2), vec!["one", "two ..."]);
+        assert_eq!(split_message("one (split) a b c d e", 3, 2), vec!["one", "(1/1) a b ..."]);
+    }
+
+    fn assert_math(expr: &str, expected: f64) {
+        let value = eval_math(expr).unwrap();
+        assert!((value - expected).abs() < 1e-9, "{} = {}, expected {}", expr, value, expected);
+    }
+
+    #[test]
+    fn math_precedence() {
+        assert_math("1 + 2 * 3", 7.0);
+        assert_math("(1 + 2) * 3", 9.0);
+        assert_math("10 - 4 - 3", 3.0);
+        assert_math("12 / 4 / 3", 1.0);
+        assert_math("2 ^ 3 ^ 2", 512.0);
+        assert_math("2 * 3 ^ 2", 18.0);
+        assert_math("7 % 4 + 1", 4.0);
+        assert_math("2 * -3", -6.0);
+        assert_math("--2", 2.0);
+        assert_math("((2.5))", 2.5);
+    }
+
+    #[test]
+    fn math_division_by_zero() {
+        assert_eq!(eval_math("1 / 0"), None);
+        assert_eq!(eval_math("5 % 0"), None);
+        assert_eq!(eval_math("1 / (2 - 2)"), None);
+    }
+
+    #[test]
+    fn math_durations() {
+        assert_math("2h30m", 2.5);
+        assert_math("1day2h", 26.0);
+        assert_math("90m * 2", 3.0);
+        assert_math("12 / 2h30m", 4.8);
+    }
+
+    #[test]
+    fn math_invalid() {
+        assert_eq!(eval_math(""), None);
+        assert_eq!(eval_math("1 +"), None);
+        assert_eq!(eval_math("(1 + 2"), None);
+        assert_eq!(eval_math("1 + 2)"), None);
+        assert_eq!(eval_math("1 2"), None);
+        assert_eq!(eval_math("2 apples"), None);
+        assert_eq!(eval_math("1h5"), None);
+        assert_eq!(eval_math("10 ^ 400"), None);
+    }
+}
@@ -3,6 +3,7 @@ extern crate jsonwebtoken as jwt;
 use crate::types::*;
 use std::collections::HashMap;
 use std::time::{SystemTime};
+use chrono::Utc;
 use bcrypt::{DEFAULT_COST, hash, verify};
 use rand::Rng;
 use rand::distributions::Alphanumeric;
@@ -18,6 +19,8 @@ use rocket_contrib::templates::Template;
 use jwt::{encode, decode, Header, Validation};
 
 const AGENT_VERSION: &str = "1.0.0";
+const FILTERS: [&str; 3] = ["caps", "symbols", "length"];
+const SUBS: [&str; 5] = ["sub", "resub", "subgift", "mysterygift", "anonsubgift"];
 
 impl<'a, 'r> FromRequest<'a, 'r> for Auth {
     type Error = AuthError;
@@ -422,13 +425,22 @@ pub fn data(con: RedisConnection, auth: Auth) -> Json<ApiData> {
             //log_error(Some(Right(vec![&auth.channel])), "data", &e.to_string(), db.clone());
             let fields: HashMap<String, String> = HashMap::new();
             let commands: HashMap<String, String> = HashMap::new();
+            let cooldowns: HashMap<String, HashMap<String,String>> = HashMap::new();
             let notices: HashMap<String, Vec<String>> = HashMap::new();
             let settings: HashMap<String, String> = HashMap::new();
             let blacklist: HashMap<String, HashMap<String,String>> = HashMap::new();
             let keywords: HashMap<String, HashMap<String,String>> = HashMap::new();
             let songreqs: Vec<(String,String,String)> = Vec::new();
             let integrations: HashMap<String, HashMap<String,String>> = HashMap::new();
-            let json = ApiData { channel: auth.channel, state: state, fields: fields, commands: commands, notices: notices, settings: settings, blacklist: blacklist, keywords: keywords, songreqs: songreqs, integrations: integrations };
+            let counters: HashMap<String,String> = HashMap::new();
+            let quotes: HashMap<String, HashMap<String,String>> = HashMap::new();
+            let points: Vec<(String,i64)> = Vec::new();
+            let greetings: HashMap<String, HashMap<String,String>> = HashMap::new();
+            let subs: HashMap<String,String> = HashMap::new();
+            let events: HashMap<String, HashMap<String,String>> = HashMap::new();
+            let filters: HashMap<String, HashMap<String,String>> = HashMap::new();
+            let permissions: HashMap<String, String> = HashMap::new();
+            let json = ApiData { channel: auth.channel, state: state, fields: fields, commands: commands, cooldowns: cooldowns, notices: notices, settings: settings, blacklist: blacklist, keywords: keywords, songreqs: songreqs, integrations: integrations, permissions: permissions, filters: filters, events: events, subs: subs, greetings: greetings, points: points, quotes: quotes, counters: counters };
             return Json(json);
         }
         Ok(mut rsp) => {
@@ -438,23 +450,39 @@ pub fn data(con: RedisConnection, auth: Auth) -> Json<ApiData> {
                     //log_error(Some(Right(vec![&auth.channel])), "data", &e.to_string(), db.clone());
                     let fields: HashMap<String, String> = HashMap::new();
                     let commands: HashMap<String, String> = HashMap::new();
+                    let cooldowns: HashMap<String, HashMap<String,String>> = HashMap::new();
                     let notices: HashMap<String, Vec<String>> = HashMap::new();
                     let settings: HashMap<String, String> = HashMap::new();
                     let blacklist: HashMap<String, HashMap<String,String>> = HashMap::new();
                     let keywords: HashMap<String, HashMap<String,String>> = HashMap::new();
                     let songreqs: Vec<(String,String,String)> = Vec::new();
                     let integrations: HashMap<String, HashMap<String,String>> = HashMap::new();
-                    let json = ApiData { channel: auth.channel, state: state, fields: fields, commands: commands, notices: notices, settings: settings, blacklist: blacklist, keywords: keywords, songreqs: songreqs, integrations: integrations };
+                    let counters: HashMap<String,String> = HashMap::new();
+                    let quotes: HashMap<String, HashMap<String,String>> = HashMap::new();
+                    let points: Vec<(String,i64)> = Vec::new();
+                    let greetings: HashMap<String, HashMap<String,String>> = HashMap::new();
+                    let subs: HashMap<String,String> = HashMap::new();
+                    let events: HashMap<String, HashMap<String,String>> = HashMap::new();
+                    let filters: HashMap<String, HashMap<String,String>> = HashMap::new();
+                    let permissions: HashMap<String, String> = HashMap::new();
+                    let json = ApiData { channel: auth.channel, state: state, fields: fields, commands: commands, cooldowns: cooldowns, notices: notices, settings: settings, blacklist: blacklist, keywords: keywords, songreqs: songreqs, integrations: integrations, permissions: permissions, filters: filters, events: events, subs: subs, greetings: greetings, points: points, quotes: quotes, counters: counters };
                     return Json(json);
                 }
                 Ok(json) => {
                     let mut fields: HashMap<String, String> = HashMap::new();
                     let mut commands: HashMap<String, String> = HashMap::new();
+                    let mut cooldowns: HashMap<String, HashMap<String,String>> = HashMap::new();
                     let mut notices: HashMap<String, Vec<String>> = HashMap::new();
                     let mut blacklist: HashMap<String, HashMap<String,String>> = HashMap::new();
                     let mut keywords: HashMap<String, HashMap<String,String>> = HashMap::new();
                     let mut songreqs: Vec<(String,String,String)> = Vec::new();
                     let mut integrations: HashMap<String, HashMap<String,String>> = HashMap::new();
+                    let mut quotes: HashMap<String, HashMap<String,String>> = HashMap::new();
+                    let mut points: Vec<(String,i64)> = Vec::new();
+                    let mut greetings: HashMap<String, HashMap<String,String>> = HashMap::new();
+                    let mut subs: HashMap<String,String> = HashMap::new();
+                    let mut events: HashMap<String, HashMap<String,String>> = HashMap::new();
+                    let mut filters: HashMap<String, HashMap<String,String>> = HashMap::new();
                     let mut twitch: HashMap<String,String> = HashMap::new();
                     let mut patreon: HashMap<String,String> = HashMap::new();
                     let mut spotify: HashMap<String,String> = HashMap::new();
@@ -495,6 +523,12 @@ pub fn data(con: RedisConnection, auth: Auth) -> Json<ApiData> {
                         let res: Result<String,_> = redis::cmd("HGET").arg(format!("channel:{}:commands:{}", &auth.channel, cmd[3])).arg("message").query(&*con);
                         if let Ok(message) = res {
                             commands.insert(cmd[3].to_owned(), message);
+                            let mut cooldown: HashMap<String,String> = HashMap::new();
+                            for field in ["cooldown", "user_cooldown", "cooldown_message"].iter() {
+                                let res: Result<String,_> = redis::cmd("HGET").arg(format!("channel:{}:commands:{}", &auth.channel, cmd[3])).arg(*field).query(&*con);
+                                if let Ok(value) = res { cooldown.insert(field.to_string(), value); }
+                            }
+                            cooldowns.insert(cmd[3].to_owned(), cooldown);
                         }
                     }
 
@@ -514,6 +548,64 @@ pub fn data(con: RedisConnection, auth: Auth) -> Json<ApiData> {
                         blacklist.insert(key[4].to_owned(), data);
                     }
 
+                    let keys: Vec<String> = redis::cmd("KEYS").arg(format!("channel:{}:events:bits:*", &auth.channel)).query(&*con).unwrap();
+                    for key in keys {
+                        let key: Vec<&str> = key.split(":").collect();
+                        let mut data: HashMap<String,String> = redis::cmd("HGETALL").arg(format!("channel:{}:events:bits:{}", &auth.channel, key[4])).query(&*con).unwrap();
+                        let field = match data.get("type").map(|t| t.as_ref()) {
+                            Some("local") => "actions",
+                            Some("command") => "command",
+                            Some("counter") => "counter",
+                            Some("thanks") => "message",
+                            _ => ""
+                        };
+                        let value = data.get(field).cloned().unwrap_or("".to_owned());
+                        data.insert("value".to_owned(), value);
+                        events.insert(key[4].to_owned(), data);
+                    }
+
+                    for filter in FILTERS.iter() {
+                        let mut data: HashMap<String,String> = HashMap::new();
+                        let enabled: String = redis::cmd("GET").arg(format!("channel:{}:moderation:{}", &auth.channel, filter)).query(&*con).unwrap_or("false".to_owned());
+                        data.insert("enabled".to_owned(), enabled);
+                        for field in ["limit", "trigger", "subs"].iter() {
+                            let res: Result<String,_> = redis::cmd("GET").arg(format!("channel:{}:moderation:{}:{}", &auth.channel, filter, field)).query(&*con);
+                            if let Ok(value) = res { data.insert(field.to_string(), value); }
+                        }
+                        filters.insert(filter.to_string(), data);
+                    }
+
+                    for sub in SUBS.iter() {
+                        let message = settings.get(&format!("channel:{}-message", sub)).cloned().unwrap_or("".to_owned());
+                        subs.insert(sub.to_string(), message);
+                    }
+
+                    let keys: Vec<String> = redis::cmd("KEYS").arg(format!("channel:{}:greetings:*", &auth.channel)).query(&*con).unwrap();
+                    for key in keys {
+                        let key: Vec<&str> = key.split(":").collect();
+                        let data: HashMap<String,String> = redis::cmd("HGETALL").arg(format!("channel:{}:greetings:{}", &auth.channel, key[3])).query(&*con).unwrap();
+                        greetings.insert(key[3].to_owned(), data);
+                    }
+
+                    let counters: HashMap<String,String> = redis::cmd("HGETALL").arg(format!("channel:{}:counters", &auth.channel)).query(&*con).unwrap_or(HashMap::new());
+
+                    let keys: Vec<String> = redis::cmd("KEYS").arg(format!("channel:{}:quotes:*", &auth.channel)).query(&*con).unwrap();
+                    for key in keys {
+                        let key: Vec<&str> = key.split(":").collect();
+                        let data: HashMap<String,String> = redis::cmd("HGETALL").arg(format!("channel:{}:quotes:{}", &auth.channel, key[3])).query(&*con).unwrap();
+                        quotes.insert(key[3].to_owned(), data);
+                    }
+
+                    // the leaderboard only holds the top 25 viewers
+                    let balances: HashMap<String,String> = redis::cmd("HGETALL").arg(format!("channel:{}:points", &auth.channel)).query(&*con).unwrap_or(HashMap::new());
+                    let excluded = settings.get("watchtime:blacklist").cloned().unwrap_or("".to_owned()).to_lowercase();
+                    for (nick, balance) in balances.iter() {
+                        if excluded.split_whitespace().any(|name| name == nick) { continue }
+                        if let Ok(balance) = balance.parse::<i64>() { points.push((nick.to_owned(), balance)) }
+                    }
+                    points.sort_by(|a,b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+                    points.truncate(25);
+
                     let keys: Vec<String> = redis::cmd("KEYS").arg(format!("channel:{}:keywords:*", &auth.channel)).query(&*con).unwrap();
                     for key in keys {
                         let key: Vec<&str> = key.split(":").collect();
@@ -530,7 +622,9 @@ pub fn data(con: RedisConnection, auth: Auth) -> Json<ApiData> {
                         songreqs.push((src,title,nick));
                     }
 
-                    let json = ApiData { channel: auth.channel, state: state, fields: fields, commands: commands, notices: notices, settings: settings, blacklist: blacklist, keywords: keywords, songreqs: songreqs, integrations: integrations };
+                    let permissions: HashMap<String,String> = redis::cmd("HGETALL").arg(format!("channel:{}:permissions", &auth.channel)).query(&*con).unwrap_or(HashMap::new());
+
+                    let json = ApiData { channel: auth.channel, state: state, fields: fields, commands: commands, cooldowns: cooldowns, notices: notices, settings: settings, blacklist: blacklist, keywords: keywords, songreqs: songreqs, integrations: integrations, permissions: permissions, filters: filters, events: events, subs: subs, greetings: greetings, points: points, quotes: quotes, counters: counters };
                     return Json(json);
                 }
             }
@@ -543,6 +637,7 @@ pub fn public_data(con: RedisConnection, channel: String) -> Json<ApiData> {
     let res: Result<String,_> = redis::cmd("GET").arg(format!("channel:{}:id", channel)).query(&*con);
     if let Ok(_id) = res {
         let mut commands: HashMap<String, String> = HashMap::new();
+        let cooldowns: HashMap<String, HashMap<String,String>> = HashMap::new();
         let fields: HashMap<String, String> = HashMap::new();
         let settings: HashMap<String, String> = HashMap::new();
         let notices: HashMap<String, Vec<String>> = HashMap::new();
@@ -550,6 +645,14 @@ pub fn public_data(con: RedisConnection, channel: String) -> Json<ApiData> {
         let keywords: HashMap<String, HashMap<String,String>> = HashMap::new();
         let songreqs: Vec<(String,String,String)> = Vec::new();
         let integrations: HashMap<String, HashMap<String,String>> = HashMap::new();
+        let counters: HashMap<String,String> = HashMap::new();
+        let mut quotes: HashMap<String, HashMap<String,String>> = HashMap::new();
+        let points: Vec<(String,i64)> = Vec::new();
+        let greetings: HashMap<String, HashMap<String,String>> = HashMap::new();
+        let subs: HashMap<String,String> = HashMap::new();
+        let events: HashMap<String, HashMap<String,String>> = HashMap::new();
+        let filters: HashMap<String, HashMap<String,String>> = HashMap::new();
+        let permissions: HashMap<String, String> = HashMap::new();
 
         let keys: Vec<String> = redis::cmd("KEYS").arg(format!("channel:{}:commands:*", channel)).query(&*con).unwrap();
         for key in keys.iter() {
@@ -560,18 +663,35 @@ pub fn public_data(con: RedisConnection, channel: String) -> Json<ApiData> {
             }
         }
 
-        let json = ApiData { channel: "".to_owned(), state: "".to_owned(), fields: fields, commands: commands, notices: notices, settings: settings, blacklist: blacklist, keywords: keywords, songreqs: songreqs, integrations: integrations };
+        let keys: Vec<String> = redis::cmd("KEYS").arg(format!("channel:{}:quotes:*", channel)).query(&*con).unwrap();
+        for key in keys.iter() {
+            let key: Vec<&str> = key.split(":").collect();
+            let data: HashMap<String,String> = redis::cmd("HGETALL").arg(format!("channel:{}:quotes:{}", channel, key[3])).query(&*con).unwrap();
+            let data: HashMap<String,String> = data.into_iter().filter(|(field, _)| field != "nick").collect();
+            quotes.insert(key[3].to_owned(), data);
+        }
+
+        let json = ApiData { channel: "".to_owned(), state: "".to_owned(), fields: fields, commands: commands, cooldowns: cooldowns, notices: notices, settings: settings, blacklist: blacklist, keywords: keywords, songreqs: songreqs, integrations: integrations, permissions: permissions, filters: filters, events: events, subs: subs, greetings: greetings, points: points, quotes: quotes, counters: counters };
         return Json(json);
     } else {
         let fields: HashMap<String, String> = HashMap::new();
         let commands: HashMap<String, String> = HashMap::new();
+        let cooldowns: HashMap<String, HashMap<String,String>> = HashMap::new();
         let notices: HashMap<String, Vec<String>> = HashMap::new();
         let settings: HashMap<String, String> = HashMap::new();
         let blacklist: HashMap<String, HashMap<String,String>> = HashMap::new();
         let keywords: HashMap<String, HashMap<String,String>> = HashMap::new();
         let songreqs: Vec<(String,String,String)> = Vec::new();
         let integrations: HashMap<String, HashMap<String,String>> = HashMap::new();
-        let json = ApiData { channel: "".to_owned(), state: "".to_owned(), fields: fields, commands: commands, notices: notices, settings: settings, blacklist: blacklist, keywords: keywords, songreqs: songreqs, integrations: integrations };
+        let counters: HashMap<String,String> = HashMap::new();
+        let quotes: HashMap<String, HashMap<String,String>> = HashMap::new();
+        let points: Vec<(String,i64)> = Vec::new();
+        let greetings: HashMap<String, HashMap<String,String>> = HashMap::new();
+        let subs: HashMap<String,String> = HashMap::new();
+        let events: HashMap<String, HashMap<String,String>> = HashMap::new();
+        let filters: HashMap<String, HashMap<String,String>> = HashMap::new();
+        let permissions: HashMap<String, String> = HashMap::new();
+        let json = ApiData { channel: "".to_owned(), state: "".to_owned(), fields: fields, commands: commands, cooldowns: cooldowns, notices: notices, settings: settings, blacklist: blacklist, keywords: keywords, songreqs: songreqs, integrations: integrations, permissions: permissions, filters: filters, events: events, subs: subs, greetings: greetings, points: points, quotes: quotes, counters: counters };
         return Json(json);
     }
 }
@@ -734,6 +854,33 @@ pub fn logs(con: RedisConnection, data: Form<ApiLogsReq>, auth: Auth) -> Json<Ap
     }
 }
 
+#[post("/api/moderation_log", data="<data>")]
+pub fn moderation_log(con: RedisConnection, data: Form<ApiModerationLogReq>, auth: Auth) -> Json<ApiRsp> {
+    let res: Result<usize,_> = data.num.parse();
+    if let Ok(num) = res {
+        let nick = data.nick.clone().unwrap_or("".to_owned()).to_lowercase();
+        let rule = data.rule.clone().unwrap_or("".to_owned()).to_lowercase();
+        let entries: Vec<String> = redis::cmd("lrange").arg(format!("channel:{}:moderation:log", &auth.channel)).arg(0).arg(-1).query(&*con).unwrap();
+        let lines: Vec<String> = entries.iter().filter_map(|entry| {
+            let entry: Result<ModerationEntry,_> = serde_json::from_str(entry);
+            match entry {
+                Err(_) => None,
+                Ok(entry) => {
+                    if (!nick.is_empty() && entry.nick != nick) || (!rule.is_empty() && entry.rule != rule) { return None }
+                    let mut action = entry.action.to_owned();
+                    if !entry.length.is_empty() { action = format!("{} {}s", entry.action, entry.length) }
+                    Some(format!("[{}] [{}] [{}] {}: {}", entry.timestamp, entry.rule, action, entry.nick, entry.message))
+                }
+            }
+        }).take(num).collect();
+        let json = ApiRsp { success: true, success_value: Some(lines.join("\n")), field: Some("modlog".to_owned()), error_message: None };
+        return Json(json);
+    } else {
+        let json = ApiRsp { success: false, success_value: None, field: Some("modlog".to_owned()), error_message: Some("invalid input".to_owned()) };
+        return Json(json);
+    }
+}
+
 #[post("/api/password", data="<data>")]
 pub fn password(con: RedisConnection, data: Form<ApiPasswordReq>, auth: Auth) -> Json<ApiRsp> {
     if data.password.is_empty() {
@@ -872,6 +1019,21 @@ pub fn new_command(con: RedisConnection, data: Form<ApiSaveCommandReq>, auth: Au
 pub fn save_command(con: RedisConnection, data: Form<ApiSaveCommandReq>, auth: Auth) -> Json<ApiRsp> {
     if !data.command.is_empty() && !data.message.is_empty() {
         redis::cmd("HSET").arg(format!("channel:{}:commands:{}", &auth.channel, &data.command)).arg("message").arg(&data.message).execute(&*con);
+        if let Some(cooldown) = &data.cooldown {
+            let res: Result<u32,_> = cooldown.parse();
+            if let Ok(_num) = res { redis::cmd("HSET").arg(format!("channel:{}:commands:{}", &auth.channel, &data.command)).arg("cooldown").arg(cooldown).execute(&*con); }
+        }
+        if let Some(cooldown) = &data.user_cooldown {
+            let res: Result<u32,_> = cooldown.parse();
+            if let Ok(_num) = res { redis::cmd("HSET").arg(format!("channel:{}:commands:{}", &auth.channel, &data.command)).arg("user_cooldown").arg(cooldown).execute(&*con); }
+        }
+        if let Some(message) = &data.cooldown_message {
+            if message.is_empty() {
+                redis::cmd("HDEL").arg(format!("channel:{}:commands:{}", &auth.channel, &data.command)).arg("cooldown_message").execute(&*con);
+            } else {
+                redis::cmd("HSET").arg(format!("channel:{}:commands:{}", &auth.channel, &data.command)).arg("cooldown_message").arg(message).execute(&*con);
+            }
+        }
         let json = ApiRsp { success: true, success_value: None, field: None, error_message: None };
         return Json(json);
     } else {
@@ -884,6 +1046,32 @@ pub fn save_command(con: RedisConnection, data: Form<ApiSaveCommandReq>, auth: A
 pub fn trash_command(con: RedisConnection, data: Form<ApiTrashCommandReq>, auth: Auth) -> Json<ApiRsp> {
     if !data.command.is_empty() {
         redis::cmd("DEL").arg(format!("channel:{}:commands:{}", &auth.channel, &data.command)).execute(&*con);
+        redis::cmd("HDEL").arg(format!("channel:{}:permissions", &auth.channel)).arg(&data.command).execute(&*con);
+        let json = ApiRsp { success: true, success_value: None, field: None, error_message: None };
+        return Json(json);
+    } else {
+        let json = ApiRsp { success: false, success_value: None, field: None, error_message: None };
+        return Json(json);
+    }
+}
+
+#[post("/api/save_permission", data="<data>")]
+pub fn save_permission(con: RedisConnection, data: Form<ApiSavePermissionReq>, auth: Auth) -> Json<ApiRsp> {
+    if let Some(role) = Role::parse(&data.role) {
+        if !data.command.is_empty() {
+            redis::cmd("HSET").arg(format!("channel:{}:permissions", &auth.channel)).arg(&data.command.to_lowercase()).arg(role.as_str()).execute(&*con);
+            let json = ApiRsp { success: true, success_value: None, field: None, error_message: None };
+            return Json(json);
+        }
+    }
+    let json = ApiRsp { success: false, success_value: None, field: None, error_message: None };
+    return Json(json);
+}
+
+#[post("/api/trash_permission", data="<data>")]
+pub fn trash_permission(con: RedisConnection, data: Form<ApiTrashPermissionReq>, auth: Auth) -> Json<ApiRsp> {
+    if !data.command.is_empty() {
+        redis::cmd("HDEL").arg(format!("channel:{}:permissions", &auth.channel)).arg(&data.command).execute(&*con);
         let json = ApiRsp { success: true, success_value: None, field: None, error_message: None };
         return Json(json);
     } else {
@@ -1000,12 +1188,196 @@ pub fn trash_blacklist(con: RedisConnection, data: Form<ApiTrashBlacklistReq>, a
     }
 }
 
+#[post("/api/save_filter", data="<data>")]
+pub fn save_filter(con: RedisConnection, data: Form<ApiSaveFilterReq>, auth: Auth) -> Json<ApiRsp> {
+    // caps and symbols take a percentage, length a number of characters
+    let limit = if data.filter == "length" { data.limit.parse::<usize>().is_ok() } else { data.limit.parse::<f32>().is_ok() };
+    // caps and symbols only apply to messages of at least the trigger length, so they can't be saved without one
+    let trigger: Option<f32> = data.trigger.as_ref().and_then(|trigger| trigger.parse().ok());
+    let needs_trigger = data.filter == "caps" || data.filter == "symbols";
+    if FILTERS.contains(&data.filter.as_ref()) && limit && (trigger.is_some() || !needs_trigger) {
+        redis::cmd("SET").arg(format!("channel:{}:moderation:{}", &auth.channel, &data.filter)).arg("true").execute(&*con);
+        redis::cmd("SET").arg(format!("channel:{}:moderation:{}:limit", &auth.channel, &data.filter)).arg(&data.limit).execute(&*con);
+        if let (Some(_num), Some(trigger)) = (trigger, &data.trigger) {
+            redis::cmd("SET").arg(format!("channel:{}:moderation:{}:trigger", &auth.channel, &data.filter)).arg(trigger).execute(&*con);
+        }
+        if let Some(subs) = &data.subs {
+            redis::cmd("SET").arg(format!("channel:{}:moderation:{}:subs", &auth.channel, &data.filter)).arg(subs).execute(&*con);
+        }
+        let json = ApiRsp { success: true, success_value: None, field: None, error_message: None };
+        return Json(json);
+    } else {
+        let json = ApiRsp { success: false, success_value: None, field: None, error_message: None };
+        return Json(json);
+    }
+}
+
+#[post("/api/trash_filter", data="<data>")]
+pub fn trash_filter(con: RedisConnection, data: Form<ApiTrashFilterReq>, auth: Auth) -> Json<ApiRsp> {
+    if FILTERS.contains(&data.filter.as_ref()) {
+        redis::cmd("DEL").arg(format!("channel:{}:moderation:{}", &auth.channel, &data.filter)).execute(&*con);
+        let json = ApiRsp { success: true, success_value: None, field: None, error_message: None };
+        return Json(json);
+    } else {
+        let json = ApiRsp { success: false, success_value: None, field: None, error_message: None };
+        return Json(json);
+    }
+}
+
+#[post("/api/save_event", data="<data>")]
+pub fn save_event(con: RedisConnection, data: Form<ApiSaveEventReq>, auth: Auth) -> Json<ApiRsp> {
+    let bits: Result<u16,_> = data.bits.parse();
+    let field = match data.etype.as_ref() {
+        "local" => Some("actions"),
+        "command" => Some("command"),
+        "counter" => Some("counter"),
+        "thanks" => Some("message"),
+        "songreq" => Some(""),
+        _ => None
+    };
+    if let (Ok(bits), Some(field)) = (bits, field) {
+        if field.is_empty() || !data.value.is_empty() {
+            redis::cmd("DEL").arg(format!("channel:{}:events:bits:{}", &auth.channel, bits)).execute(&*con);
+            redis::cmd("HSET").arg(format!("channel:{}:events:bits:{}", &auth.channel, bits)).arg("type").arg(&data.etype).execute(&*con);
+            if !field.is_empty() { redis::cmd("HSET").arg(format!("channel:{}:events:bits:{}", &auth.channel, bits)).arg(field).arg(&data.value).execute(&*con); }
+            let json = ApiRsp { success: true, success_value: None, field: None, error_message: None };
+            return Json(json);
+        }
+    }
+    let json = ApiRsp { success: false, success_value: None, field: None, error_message: None };
+    return Json(json);
+}
+
+#[post("/api/trash_event", data="<data>")]
+pub fn trash_event(con: RedisConnection, data: Form<ApiTrashEventReq>, auth: Auth) -> Json<ApiRsp> {
+    if !data.bits.is_empty() {
+        redis::cmd("DEL").arg(format!("channel:{}:events:bits:{}", &auth.channel, &data.bits)).execute(&*con);
+        let json = ApiRsp { success: true, success_value: None, field: None, error_message: None };
+        return Json(json);
+    } else {
+        let json = ApiRsp { success: false, success_value: None, field: None, error_message: None };
+        return Json(json);
+    }
+}
+
+#[post("/api/save_greeting", data="<data>")]
+pub fn save_greeting(con: RedisConnection, data: Form<ApiSaveGreetingReq>, auth: Auth) -> Json<ApiRsp> {
+    let hours: Result<u16,_> = data.hours.parse();
+    if let (false, false, Ok(hours)) = (data.nick.is_empty(), data.message.is_empty(), hours) {
+        let nick = data.nick.to_lowercase().replace("@", "");
+        redis::cmd("HSET").arg(format!("channel:{}:greetings:{}", &auth.channel, &nick)).arg("message").arg(&data.message).execute(&*con);
+        redis::cmd("HSET").arg(format!("channel:{}:greetings:{}", &auth.channel, &nick)).arg("hours").arg(hours).execute(&*con);
+        let json = ApiRsp { success: true, success_value: None, field: None, error_message: None };
+        return Json(json);
+    } else {
+        let json = ApiRsp { success: false, success_value: None, field: None, error_message: None };
+        return Json(json);
+    }
+}
+
+#[post("/api/trash_greeting", data="<data>")]
+pub fn trash_greeting(con: RedisConnection, data: Form<ApiTrashGreetingReq>, auth: Auth) -> Json<ApiRsp> {
+    if !data.nick.is_empty() {
+        redis::cmd("DEL").arg(format!("channel:{}:greetings:{}", &auth.channel, &data.nick)).execute(&*con);
+        let json = ApiRsp { success: true, success_value: None, field: None, error_message: None };
+        return Json(json);
+    } else {
+        let json = ApiRsp { success: false, success_value: None, field: None, error_message: None };
+        return Json(json);
+    }
+}
+
+#[get("/api/<channel>/poll")]
+pub fn poll(con: RedisConnection, channel: String) -> Json<ApiPoll> {
+    let data: HashMap<String,String> = redis::cmd("HGETALL").arg(format!("channel:{}:poll", channel)).query(&*con).unwrap_or(HashMap::new());
+    let options: Vec<String> = redis::cmd("LRANGE").arg(format!("channel:{}:poll:options", channel)).arg(0).arg(-1).query(&*con).unwrap_or(Vec::new());
+    let votes: HashMap<String,String> = redis::cmd("HGETALL").arg(format!("channel:{}:poll:votes", channel)).query(&*con).unwrap_or(HashMap::new());
+    let options: Vec<(String,u32)> = options.iter().enumerate().map(|(i, option)| {
+        let count = votes.values().filter(|vote| **vote == (i + 1).to_string()).count();
+        (option.to_owned(), count as u32)
+    }).collect();
+    let question = data.get("question").cloned().unwrap_or("".to_owned());
+    let ends = data.get("ends").cloned().unwrap_or("".to_owned());
+    let open = data.get("open").map_or(false, |open| open == "true");
+    let json = ApiPoll { question: question, open: open, ends: ends, options: options };
+    return Json(json);
+}
+
+#[post("/api/new_quote", data="<data>")]
+pub fn new_quote(con: RedisConnection, data: Form<ApiNewQuoteReq>, auth: Auth) -> Json<ApiRsp> {
+    if !data.quote.is_empty() {
+        let id: i64 = redis::cmd("INCR").arg(format!("channel:{}:quoteid", &auth.channel)).query(&*con).unwrap();
+        redis::cmd("HSET").arg(format!("channel:{}:quotes:{}", &auth.channel, id)).arg("quote").arg(&data.quote).execute(&*con);
+        redis::cmd("HSET").arg(format!("channel:{}:quotes:{}", &auth.channel, id)).arg("game").arg(data.game.clone().unwrap_or("".to_owned())).execute(&*con);
+        redis::cmd("HSET").arg(format!("channel:{}:quotes:{}", &auth.channel, id)).arg("date").arg(Utc::now().format("%b %-d %Y").to_string()).execute(&*con);
+        redis::cmd("HSET").arg(format!("channel:{}:quotes:{}", &auth.channel, id)).arg("nick").arg(&auth.channel).execute(&*con);
+        let json = ApiRsp { success: true, success_value: Some(id.to_string()), field: None, error_message: None };
+        return Json(json);
+    } else {
+        let json = ApiRsp { success: false, success_value: None, field: None, error_message: None };
+        return Json(json);
+    }
+}
+
+#[post("/api/save_quote", data="<data>")]
+pub fn save_quote(con: RedisConnection, data: Form<ApiSaveQuoteReq>, auth: Auth) -> Json<ApiRsp> {
+    let exists: bool = redis::cmd("EXISTS").arg(format!("channel:{}:quotes:{}", &auth.channel, &data.id)).query(&*con).unwrap_or(false);
+    if exists && !data.quote.is_empty() {
+        redis::cmd("HSET").arg(format!("channel:{}:quotes:{}", &auth.channel, &data.id)).arg("quote").arg(&data.quote).execute(&*con);
+        if let Some(game) = &data.game { redis::cmd("HSET").arg(format!("channel:{}:quotes:{}", &auth.channel, &data.id)).arg("game").arg(game).execute(&*con); }
+        let json = ApiRsp { success: true, success_value: None, field: None, error_message: None };
+        return Json(json);
+    } else {
+        let json = ApiRsp { success: false, success_value: None, field: None, error_message: None };
+        return Json(json);
+    }
+}
+
+#[post("/api/trash_quote", data="<data>")]
+pub fn trash_quote(con: RedisConnection, data: Form<ApiTrashQuoteReq>, auth: Auth) -> Json<ApiRsp> {
+    if !data.id.is_empty() {
+        redis::cmd("DEL").arg(format!("channel:{}:quotes:{}", &auth.channel, &data.id)).execute(&*con);
+        let json = ApiRsp { success: true, success_value: None, field: None, error_message: None };
+        return Json(json);
+    } else {
+        let json = ApiRsp { success: false, success_value: None, field: None, error_message: None };
+        return Json(json);
+    }
+}
+
+#[post("/api/save_counter", data="<data>")]
+pub fn save_counter(con: RedisConnection, data: Form<ApiSaveCounterReq>, auth: Auth) -> Json<ApiRsp> {
+    let value: Result<i64,_> = data.value.parse();
+    if let (false, Ok(value)) = (data.name.is_empty(), value) {
+        redis::cmd("HSET").arg(format!("channel:{}:counters", &auth.channel)).arg(&data.name).arg(value).execute(&*con);
+        let json = ApiRsp { success: true, success_value: None, field: None, error_message: None };
+        return Json(json);
+    } else {
+        let json = ApiRsp { success: false, success_value: None, field: None, error_message: None };
+        return Json(json);
+    }
+}
+
+#[post("/api/trash_counter", data="<data>")]
+pub fn trash_counter(con: RedisConnection, data: Form<ApiTrashCounterReq>, auth: Auth) -> Json<ApiRsp> {
+    if !data.name.is_empty() {
+        redis::cmd("HDEL").arg(format!("channel:{}:counters", &auth.channel)).arg(&data.name).execute(&*con);
+        let json = ApiRsp { success: true, success_value: None, field: None, error_message: None };
+        return Json(json);
+    } else {
+        let json = ApiRsp { success: false, success_value: None, field: None, error_message: None };
+        return Json(json);
+    }
+}
+
 #[post("/api/new_keyword", data="<data>")]
 pub fn new_keyword(con: RedisConnection, data: Form<ApiNewKeywordReq>, auth: Auth) -> Json<ApiRsp> {
-    if !data.regex.is_empty() && !data.command.is_empty() {
+    let message = data.message.clone().unwrap_or("".to_owned());
+    if !data.regex.is_empty() && (!data.command.is_empty() || !message.is_empty()) {
         let key = hash(&data.regex, 6).unwrap();
         redis::cmd("HSET").arg(format!("channel:{}:keywords:{}", &auth.channel, &key)).arg("regex").arg(&data.regex).execute(&*con);
         redis::cmd("HSET").arg(format!("channel:{}:keywords:{}", &auth.channel, &key)).arg("cmd").arg(&data.command).execute(&*con);
+        save_keyword_options(&con, &auth.channel, &key, &message, &data.cooldown, &data.sensitive, &data.exempt);
         let json = ApiRsp { success: true, success_value: None, field: None, error_message: None };
         return Json(json);
     } else {
@@ -1016,9 +1388,11 @@ pub fn new_keyword(con: RedisConnection, data: Form<ApiNewKeywordReq>, auth: Aut
 
 #[post("/api/save_keyword", data="<data>")]
 pub fn save_keyword(con: RedisConnection, data: Form<ApiSaveKeywordReq>, auth: Auth) -> Json<ApiRsp> {
-    if !data.key.is_empty() && !data.regex.is_empty() && !data.command.is_empty() {
+    let message = data.message.clone().unwrap_or("".to_owned());
+    if !data.key.is_empty() && !data.regex.is_empty() && (!data.command.is_empty() || !message.is_empty()) {
         redis::cmd("HSET").arg(format!("channel:{}:keywords:{}", &auth.channel, &data.key)).arg("regex").arg(&data.regex).execute(&*con);
-        redis::cmd("HSET").arg(format!("channel:{}:keywords:{}", &auth.channel, &data.key)).arg("command").arg(&data.command).execute(&*con);
+        redis::cmd("HSET").arg(format!("channel:{}:keywords:{}", &auth.channel, &data.key)).arg("cmd").arg(&data.command).execute(&*con);
+        save_keyword_options(&con, &auth.channel, &data.key, &message, &data.cooldown, &data.sensitive, &data.exempt);
         let json = ApiRsp { success: true, success_value: None, field: None, error_message: None };
         return Json(json);
     } else {
@@ -1027,6 +1401,20 @@ pub fn save_keyword(con: RedisConnection, data: Form<ApiSaveKeywordReq>, auth: A
     }
 }
 
+fn save_keyword_options(con: &RedisConnection, channel: &str, key: &str, message: &str, cooldown: &Option<String>, sensitive: &Option<String>, exempt: &Option<String>) {
+    redis::cmd("HSET").arg(format!("channel:{}:keywords:{}", channel, key)).arg("message").arg(message).execute(&**con);
+    if let Some(cooldown) = cooldown {
+        let num: Result<u32,_> = cooldown.parse();
+        if let Ok(num) = num { redis::cmd("HSET").arg(format!("channel:{}:keywords:{}", channel, key)).arg("cooldown").arg(num).execute(&**con); }
+    }
+    if let Some(sensitive) = sensitive {
+        redis::cmd("HSET").arg(format!("channel:{}:keywords:{}", channel, key)).arg("sensitive").arg(if sensitive == "true" { "true" } else { "false" }).execute(&**con);
+    }
+    if let Some(exempt) = exempt {
+        redis::cmd("HSET").arg(format!("channel:{}:keywords:{}", channel, key)).arg("exempt").arg(if exempt == "true" { "true" } else { "false" }).execute(&**con);
+    }
+}
+
 #[post("/api/trash_keyword", data="<data>")]
 pub fn trash_keyword(con: RedisConnection, data: Form<ApiTrashKeywordReq>, auth: Auth) -> Json<ApiRsp> {
     if !data.key.is_empty() {